@@ -0,0 +1,215 @@
+use rustc_serialize::json::Json;
+
+use lyrics::Line;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    In,
+    Out,
+    InOut,
+}
+
+impl Easing {
+    pub fn parse(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "in" => Some(Easing::In),
+            "out" => Some(Easing::Out),
+            "in-out" => Some(Easing::InOut),
+            _ => None,
+        }
+    }
+
+    /// Maps a linear progress between 0 and 1 to the eased progress
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::In => t * t * t,
+            Easing::Out => 1.0 - (1.0 - t).powi(3),
+            Easing::InOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (2.0 - 2.0 * t).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    /// The line pops in or out
+    None,
+    Fade,
+    /// The line slides by one line height while fading
+    Slide,
+    /// The line grows or shrinks while fading
+    Scale,
+}
+
+impl Effect {
+    pub fn parse(name: &str) -> Option<Effect> {
+        match name {
+            "none" => Some(Effect::None),
+            "fade" => Some(Effect::Fade),
+            "slide" => Some(Effect::Slide),
+            "scale" => Some(Effect::Scale),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Transition {
+    pub effect: Effect,
+    /// In milliseconds
+    pub duration: u32,
+    pub easing: Easing,
+    /// Milliseconds between the end of the appearance and the first syllable,
+    /// or between the last syllable and the beginning of the disappearance
+    pub margin: u32,
+}
+
+impl Transition {
+    pub fn none() -> Transition {
+        Transition {
+            effect: Effect::None,
+            duration: 0,
+            easing: Easing::Linear,
+            margin: 0,
+        }
+    }
+
+    /// Reads a transition from a JSON object, missing keys keep their value
+    pub fn apply(&mut self, json: &Json) {
+        if let Some(effect) = json.find("effect").and_then(|j| j.as_string()).and_then(Effect::parse) {
+            self.effect = effect;
+        }
+        if let Some(duration) = json.find("duration").and_then(|j| j.as_u64()) {
+            self.duration = duration as u32;
+        }
+        if let Some(easing) = json.find("easing").and_then(|j| j.as_string()).and_then(Easing::parse) {
+            self.easing = easing;
+        }
+        if let Some(margin) = json.find("margin").and_then(|j| j.as_u64()) {
+            self.margin = margin as u32;
+        }
+    }
+
+    fn appearance(&self, progress: f32) -> Appearance {
+        let progress = self.easing.apply(progress);
+        match self.effect {
+            Effect::None => Appearance::visible(),
+            Effect::Fade => Appearance { alpha: progress, ..Appearance::visible() },
+            Effect::Slide => {
+                Appearance {
+                    alpha: progress,
+                    offset_y: 1.0 - progress,
+                    ..Appearance::visible()
+                }
+            }
+            Effect::Scale => {
+                Appearance {
+                    alpha: progress,
+                    scale: progress,
+                    ..Appearance::visible()
+                }
+            }
+        }
+    }
+}
+
+/// How a line looks at a given time
+#[derive(Debug, Clone, Copy)]
+pub struct Appearance {
+    /// From 0, transparent, to 1
+    pub alpha: f32,
+    /// Vertical offset, in line heights
+    pub offset_y: f32,
+    pub scale: f32,
+}
+
+impl Appearance {
+    pub fn visible() -> Appearance {
+        Appearance {
+            alpha: 1.0,
+            offset_y: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Timeline of a line : it appears before its first syllable, stays while it
+/// is sung, then disappears after its last syllable
+#[derive(Debug, Clone, Copy)]
+pub struct LineTransitions {
+    pub appear: Transition,
+    pub disappear: Transition,
+}
+
+impl LineTransitions {
+    pub fn none() -> LineTransitions {
+        LineTransitions {
+            appear: Transition::none(),
+            disappear: Transition::none(),
+        }
+    }
+
+    /// How long a line stays on screen after its last syllable
+    pub fn linger(&self) -> u32 {
+        match self.disappear.effect {
+            Effect::None => 0,
+            _ => self.disappear.margin + self.disappear.duration,
+        }
+    }
+
+    /// How long before its first syllable a line starts appearing
+    pub fn lead(&self) -> u32 {
+        match self.appear.effect {
+            Effect::None => 0,
+            _ => self.appear.margin + self.appear.duration,
+        }
+    }
+
+    /// Returns how `line` looks at `time`, or None when it is hidden. Without
+    /// appearance transition an upcoming line is displayed as soon as it is
+    /// the next line of its region.
+    pub fn appearance(&self, line: &Line, time: u32) -> Option<Appearance> {
+        let begin = line.begin();
+        let end = line.end();
+        if self.appear.effect != Effect::None && time < begin {
+            let appear_end = begin.saturating_sub(self.appear.margin);
+            let appear_start = appear_end.saturating_sub(self.appear.duration);
+            if time < appear_start {
+                return None;
+            }
+            if time < appear_end {
+                let progress = progress(time - appear_start, self.appear.duration);
+                return Some(self.appear.appearance(progress));
+            }
+        }
+        if self.disappear.effect != Effect::None && time > end {
+            let disappear_start = end + self.disappear.margin;
+            let disappear_end = disappear_start + self.disappear.duration;
+            if time >= disappear_end {
+                return None;
+            }
+            if time > disappear_start {
+                let progress = progress(time - disappear_start, self.disappear.duration);
+                return Some(self.disappear.appearance(1.0 - progress));
+            }
+        }
+        Some(Appearance::visible())
+    }
+}
+
+fn progress(elapsed: u32, duration: u32) -> f32 {
+    if duration == 0 {
+        1.0
+    } else {
+        elapsed as f32 / duration as f32
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use mpv::Mpv;
+use tracks::Track;
+
+/// What is displayed behind the lyrics of a file without video
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundMode {
+    /// The cover art if there is one, else the slideshow if an image
+    /// directory is configured, else the visualisation
+    Auto,
+    Cover,
+    Slideshow,
+    Visualizer,
+    None,
+}
+
+impl BackgroundMode {
+    pub fn parse(name: &str) -> Option<BackgroundMode> {
+        match name {
+            "auto" => Some(BackgroundMode::Auto),
+            "cover" => Some(BackgroundMode::Cover),
+            "slideshow" => Some(BackgroundMode::Slideshow),
+            "visualizer" => Some(BackgroundMode::Visualizer),
+            "none" => Some(BackgroundMode::None),
+            _ => None,
+        }
+    }
+}
+
+const IMAGE_EXTENSIONS: [&'static str; 6] = ["png", "jpg", "jpeg", "bmp", "gif", "webp"];
+
+/// Images of a directory added one after the other as video tracks, mpv
+/// displays them over the audio as it does cover art
+pub struct Slideshow {
+    images: Vec<PathBuf>,
+    next: usize,
+    interval: Duration,
+    shown: Option<Instant>,
+    /// Video track of the image displayed
+    track: Option<i64>,
+}
+
+impl Slideshow {
+    /// Lists the images of `directory`, returns None if there is none.
+    /// `interval` is in milliseconds.
+    pub fn new(directory: &Path, interval: u32) -> Option<Slideshow> {
+        let mut images: Vec<PathBuf> = match fs::read_dir(directory) {
+            Ok(entries) => {
+                entries.filter_map(|entry| entry.ok())
+                       .map(|entry| entry.path())
+                       .filter(|path| {
+                           path.extension()
+                               .and_then(|extension| extension.to_str())
+                               .map(|extension| {
+                                   IMAGE_EXTENSIONS.contains(&&*extension.to_lowercase())
+                               })
+                               .unwrap_or(false)
+                       })
+                       .collect()
+            }
+            Err(e) => {
+                warn!("Cannot read background directory {} : {}", directory.display(), e);
+                return None;
+            }
+        };
+        if images.is_empty() {
+            return None;
+        }
+        images.sort();
+        Some(Slideshow {
+            images: images,
+            next: 0,
+            interval: Duration::from_millis(interval as u64),
+            shown: None,
+            track: None,
+        })
+    }
+
+    /// Shows the next image once the current one has been displayed long
+    /// enough, the first one right away. The track of the previous image is
+    /// removed once the new one is selected.
+    pub fn update(&mut self, mpv: &Mpv) {
+        if self.shown.map(|shown| shown.elapsed() < self.interval).unwrap_or(false) {
+            return;
+        }
+        let image = &self.images[self.next];
+        match mpv.command(&["video-add", &*image.to_string_lossy(), "select"]) {
+            Ok(()) => {
+                if let Some(previous) = self.track {
+                    if let Err(e) = mpv.command(&["video-remove", &previous.to_string()]) {
+                        warn!("Failed to remove the previous image : {}", e);
+                    }
+                }
+                self.track = mpv.get_property::<i64>("vid").ok();
+            }
+            Err(e) => warn!("Failed to display {} : {}", image.display(), e),
+        }
+        self.next = (self.next + 1) % self.images.len();
+        self.shown = Some(Instant::now());
+    }
+}
+
+/// Background of the file being played
+pub enum Background {
+    /// Displayed by mpv, nothing to do
+    Cover,
+    Slideshow(Slideshow),
+    Visualizer,
+}
+
+impl Background {
+    /// Chooses the background of a file from its tracks, returns None for
+    /// files with a video, or when there is nothing to display
+    pub fn choose(mode: BackgroundMode,
+                  tracks: &[Track],
+                  image_directory: Option<&Path>,
+                  slideshow_interval: u32)
+                  -> Option<Background> {
+        if !::tracks::is_audio_only(tracks) {
+            return None;
+        }
+        let has_cover = tracks.iter().any(|track| track.albumart);
+        let slideshow = || {
+            image_directory.and_then(|directory| Slideshow::new(directory, slideshow_interval))
+                           .map(Background::Slideshow)
+        };
+        match mode {
+            BackgroundMode::Auto if has_cover => Some(Background::Cover),
+            BackgroundMode::Auto => slideshow().or(Some(Background::Visualizer)),
+            BackgroundMode::Cover if has_cover => Some(Background::Cover),
+            BackgroundMode::Slideshow => slideshow(),
+            BackgroundMode::Visualizer => Some(Background::Visualizer),
+            _ => None,
+        }
+    }
+
+    /// Starts displaying the background, `visualizer` is a lavfi filter
+    /// turning the audio into a video
+    pub fn start(&mut self, mpv: &Mpv, tracks: &[Track], visualizer: &str) {
+        match *self {
+            Background::Cover => {}
+            Background::Slideshow(ref mut slideshow) => slideshow.update(mpv),
+            Background::Visualizer => {
+                let audio = match tracks.iter().find(|track| track.kind == "audio") {
+                    Some(audio) => audio.id,
+                    None => return,
+                };
+                // the audio is split between the output and the visualisation
+                let graph = format!("[aid{}] asplit [ao] [visualized]; [visualized] {} [vo]",
+                                    audio,
+                                    visualizer);
+                if let Err(e) = mpv.set_property("lavfi-complex", &*graph) {
+                    warn!("Failed to start the visualisation : {}", e);
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, mpv: &Mpv) {
+        if let Background::Slideshow(ref mut slideshow) = *self {
+            slideshow.update(mpv);
+        }
+    }
+
+    /// Stops the visualisation, which would otherwise apply to the next file
+    pub fn stop(&self, mpv: &Mpv) {
+        if let Background::Visualizer = *self {
+            if let Err(e) = mpv.set_property("lavfi-complex", "") {
+                warn!("Failed to stop the visualisation : {}", e);
+            }
+        }
+    }
+}
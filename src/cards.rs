@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use rustc_serialize::json::Json;
+
+use lyrics;
+use lyrics::{Metadata, Rgb};
+use queue::QueueItem;
+
+/// Look of a full screen card
+#[derive(Debug, Clone, Copy)]
+pub struct CardTheme {
+    /// How long the card is displayed, in milliseconds
+    pub duration: u32,
+    /// Size of the text, relative to the lyrics font
+    pub font_scale: f32,
+    pub color: Rgb,
+    pub background: Rgb,
+    /// Opacity of the background, 0 to leave the video visible
+    pub background_alpha: u8,
+}
+
+impl CardTheme {
+    /// Reads a theme from a JSON object, missing keys keep their value
+    pub fn apply(&mut self, json: &Json) {
+        if let Some(duration) = json.find("duration").and_then(|j| j.as_u64()) {
+            self.duration = duration as u32;
+        }
+        if let Some(font_scale) = json.find("font_scale").and_then(|j| j.as_f64()) {
+            self.font_scale = font_scale as f32;
+        }
+        if let Some(color) = json.find("color")
+                                 .and_then(|j| j.as_string())
+                                 .and_then(lyrics::parse_color) {
+            self.color = color;
+        }
+        if let Some(background) = json.find("background")
+                                      .and_then(|j| j.as_string())
+                                      .and_then(lyrics::parse_color) {
+            self.background = background;
+        }
+        if let Some(alpha) = json.find("background_alpha").and_then(|j| j.as_u64()) {
+            self.background_alpha = ::std::cmp::min(alpha, 255) as u8;
+        }
+    }
+}
+
+/// Title of a song : its metadata title, or the name of its file
+pub fn title(metadata: &Metadata, video_path: &Path) -> String {
+    match metadata.title {
+        Some(ref title) if !title.is_empty() => title.clone(),
+        _ => {
+            video_path.file_stem()
+                      .map(|stem| stem.to_string_lossy().into_owned())
+                      .unwrap_or_default()
+        }
+    }
+}
+
+/// Lines of the card displayed when a song starts
+pub fn title_card(metadata: &Metadata, item: &QueueItem) -> Vec<String> {
+    let mut lines = vec![title(metadata, &item.path)];
+    lines.extend(metadata.artist.clone());
+    lines.extend(metadata.source.clone());
+    if !metadata.timers.is_empty() {
+        lines.push(format!("Timed by {}", metadata.timers.join(", ")));
+    }
+    if let Some(ref singer) = item.singer {
+        lines.push(format!("Sung by {}", singer));
+    }
+    lines
+}
+
+/// Reads the metadata of a song which is not loaded yet
+pub fn read_metadata(video_path: &Path) -> Metadata {
+    let (lyr_path, _) = lyrics::sidecar_paths(video_path);
+    lyrics::read_file(&lyr_path)
+        .map(|lyr| lyrics::parse_lyr(&lyr).metadata)
+        .unwrap_or_default()
+}
+
+/// Lines of the card displayed between two songs, `remaining` is the number
+/// of seconds before the next song starts
+pub fn up_next_card(metadata: &Metadata, item: &QueueItem, remaining: u32) -> Vec<String> {
+    let mut lines = vec!["Up next".to_string(), title(metadata, &item.path)];
+    lines.extend(metadata.artist.clone());
+    if let Some(ref singer) = item.singer {
+        lines.push(format!("Sung by {}", singer));
+    }
+    lines.push(format!("Starting in {}", remaining));
+    lines
+}
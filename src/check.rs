@@ -0,0 +1,422 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use sdl2_ttf::Font;
+
+use config::Config;
+use displayer::FontList;
+use gltext;
+use lyrics;
+use lyrics::{FrameRange, Line, LyricsError, Syllable};
+
+/// Lines are drawn 3 pixels away from the border of the video
+const LINE_X: u32 = 3;
+
+/// Measures lyrics as the displayer draws them
+pub trait TextMeasure {
+    /// Width of `line` in pixels at `font_size`, outline excluded
+    fn line_width(&self, line: &Line, font_size: f32) -> u32;
+}
+
+/// Measures with the OpenGL text renderer, from the font returned by
+/// `gltext::load_font`
+pub struct GlMeasure<'a>(pub &'a Font);
+
+impl<'a> TextMeasure for GlMeasure<'a> {
+    fn line_width(&self, line: &Line, font_size: f32) -> u32 {
+        gltext::text_width(self.0, &line.text(), font_size).ceil() as u32
+    }
+}
+
+/// Measures with SDL_ttf, in the closest size of the list
+impl TextMeasure for FontList {
+    fn line_width(&self, line: &Line, font_size: f32) -> u32 {
+        let font = match self.get_closest_font_set(font_size as u16) {
+            Ok(font_set) => font_set.get_regular_font(),
+            Err(_) => return 0,
+        };
+        // syllables are drawn one after the other
+        line.syllables
+            .iter()
+            .filter_map(|syllable| font.size_of(&syllable.text).ok())
+            .fold(0, |width, (syllable_width, _)| width + syllable_width)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in a lyrics file, lines and columns start at 1. A line of
+/// 0 means the problem concerns the whole file.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f,
+               "{}:{}:{}: {}: {}",
+               self.file.display(),
+               self.line,
+               self.column,
+               severity,
+               self.message)
+    }
+}
+
+struct Diagnostics {
+    list: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    fn push(&mut self,
+            severity: Severity,
+            file: &Path,
+            line: usize,
+            column: usize,
+            message: String) {
+        self.list.push(Diagnostic {
+            severity: severity,
+            file: file.to_path_buf(),
+            line: line,
+            column: column,
+            message: message,
+        });
+    }
+}
+
+/// Checks a lyr file and its frm file, without needing a window or mpv
+pub fn check_song(lyr_path: &Path,
+                  frm_path: &Path,
+                  measure: &TextMeasure,
+                  config: &Config)
+                  -> Vec<Diagnostic> {
+    let mut diagnostics = Diagnostics { list: Vec::new() };
+    check_song_into(lyr_path, frm_path, measure, config, &mut diagnostics);
+    diagnostics.list
+}
+
+fn check_song_into(lyr_path: &Path,
+                   frm_path: &Path,
+                   measure: &TextMeasure,
+                   config: &Config,
+                   diagnostics: &mut Diagnostics) {
+    let lyr = match lyrics::read_file(lyr_path) {
+        Ok(lyr) => lyr,
+        Err(e) => {
+            diagnostics.push(Severity::Error, lyr_path, 0, 0, format!("cannot read file : {}", e));
+            return;
+        }
+    };
+    let frm = match lyrics::read_file(frm_path) {
+        Ok(frm) => Some(frm),
+        Err(e) => {
+            diagnostics.push(Severity::Error, frm_path, 0, 0, format!("cannot read file : {}", e));
+            None
+        }
+    };
+    check_contents(lyr_path,
+                   &lyr,
+                   frm_path,
+                   frm.as_ref().map(|frm| &frm[..]),
+                   measure,
+                   config,
+                   diagnostics);
+}
+
+/// Checks the contents of a lyr file, and its timings when the frm file
+/// could be read
+fn check_contents(lyr_path: &Path,
+                  lyr: &str,
+                  frm_path: &Path,
+                  frm: Option<&str>,
+                  measure: &TextMeasure,
+                  config: &Config,
+                  diagnostics: &mut Diagnostics) {
+    check_colors(lyr_path, lyr, diagnostics);
+    let lines = lyrics::parse_lyr(lyr).lines;
+    check_widths(lyr_path, &lines, measure, config, diagnostics);
+
+    let frm = match frm {
+        Some(frm) => frm,
+        None => return,
+    };
+    let frames = match lyrics::parse_frm(frm) {
+        Ok(frames) => frames,
+        Err(LyricsError::InvalidFrame { line, content }) => {
+            diagnostics.push(Severity::Error,
+                             frm_path,
+                             line,
+                             1,
+                             format!("expected a begin and an end frame, found '{}'", content));
+            return;
+        }
+        Err(e) => {
+            diagnostics.push(Severity::Error, frm_path, 0, 0, e.to_string());
+            return;
+        }
+    };
+
+    let syllables: Vec<&Syllable> = lines.iter().flat_map(|l| l.syllables.iter()).collect();
+    if syllables.len() > frames.len() {
+        let first_untimed = syllables[frames.len()];
+        diagnostics.push(Severity::Error,
+                         lyr_path,
+                         first_untimed.source.lyr_line,
+                         first_untimed.source.lyr_column,
+                         format!("lyr has {} syllables but frm only has {} lines, '{}' and \
+                                  the following syllables have no timing",
+                                 syllables.len(),
+                                 frames.len(),
+                                 first_untimed.text));
+    } else if frames.len() > syllables.len() {
+        diagnostics.push(Severity::Error,
+                         frm_path,
+                         frames[syllables.len()].line,
+                         1,
+                         format!("frm has {} lines but lyr only has {} syllables",
+                                 frames.len(),
+                                 syllables.len()));
+    }
+    check_frames(lyr_path, frm_path, &syllables, &frames, diagnostics);
+}
+
+fn check_frames(lyr_path: &Path,
+                frm_path: &Path,
+                syllables: &[&Syllable],
+                frames: &[FrameRange],
+                diagnostics: &mut Diagnostics) {
+    let mut previous: Option<&FrameRange> = None;
+    for (syllable, frame) in syllables.iter().zip(frames.iter()) {
+        let name = format!("syllable '{}' ({}:{}:{})",
+                           syllable.text,
+                           lyr_path.display(),
+                           syllable.source.lyr_line,
+                           syllable.source.lyr_column);
+        if frame.end < frame.begin {
+            diagnostics.push(Severity::Error,
+                             frm_path,
+                             frame.line,
+                             1,
+                             format!("{} ends at frame {} before it begins at frame {}",
+                                     name,
+                                     frame.end,
+                                     frame.begin));
+        } else if frame.end == frame.begin {
+            diagnostics.push(Severity::Warning,
+                             frm_path,
+                             frame.line,
+                             1,
+                             format!("{} has a zero length", name));
+        }
+        if let Some(previous) = previous {
+            if frame.begin < previous.begin {
+                diagnostics.push(Severity::Error,
+                                 frm_path,
+                                 frame.line,
+                                 1,
+                                 format!("{} begins at frame {}, before the previous syllable \
+                                          (frame {})",
+                                         name,
+                                         frame.begin,
+                                         previous.begin));
+            } else if frame.begin < previous.end {
+                diagnostics.push(Severity::Warning,
+                                 frm_path,
+                                 frame.line,
+                                 1,
+                                 format!("{} overlaps the previous syllable, which ends at \
+                                          frame {}",
+                                         name,
+                                         previous.end));
+            }
+        }
+        previous = Some(frame);
+    }
+}
+
+/// Colours of `%singer` and `%color` directives the player can't read, and
+/// ignores
+fn check_colors(lyr_path: &Path, lyr: &str, diagnostics: &mut Diagnostics) {
+    for (index, line) in lyr.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        // positions of the colours among the words of the directive
+        let colors = match words.first() {
+            Some(&"%singer") => vec![3, 4],
+            Some(&"%color") => vec![2],
+            _ => continue,
+        };
+        if words[0] == "%color" && words.len() < 3 {
+            diagnostics.push(Severity::Warning,
+                             lyr_path,
+                             index + 1,
+                             1,
+                             "%color expects a colour name and a RRGGBB colour".to_string());
+        }
+        for (position, word) in words.iter().enumerate() {
+            if !colors.contains(&position) || lyrics::parse_color(word).is_some() {
+                continue;
+            }
+            let offset = word.as_ptr() as usize - line.as_ptr() as usize;
+            diagnostics.push(Severity::Warning,
+                             lyr_path,
+                             index + 1,
+                             line[..offset].chars().count() + 1,
+                             format!("invalid colour '{}', expected RRGGBB", word));
+        }
+    }
+}
+
+/// Lines wider than the video, which the lyrics font scales with
+fn check_widths(lyr_path: &Path,
+                lines: &[Line],
+                measure: &TextMeasure,
+                config: &Config,
+                diagnostics: &mut Diagnostics) {
+    let font_size = config.font_size * config.display_width as f32;
+    let margin = LINE_X + 2 * config.text_style.outline_width as u32;
+    for line in lines {
+        if line.syllables.is_empty() {
+            continue;
+        }
+        let width = measure.line_width(line, font_size) + margin;
+        if width > config.display_width {
+            let source = line.syllables[0].source;
+            diagnostics.push(Severity::Warning,
+                             lyr_path,
+                             source.lyr_line,
+                             source.lyr_column,
+                             format!("line is {} pixels wide but the video is only {} pixels \
+                                      wide",
+                                     width,
+                                     config.display_width));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use config::Config;
+    use lyrics::Line;
+    use super::{check_contents, Diagnostic, Diagnostics, Severity, TextMeasure};
+
+    /// Every character is 10 pixels wide
+    struct FixedWidth;
+
+    impl TextMeasure for FixedWidth {
+        fn line_width(&self, line: &Line, _: f32) -> u32 {
+            10 * line.text().chars().count() as u32
+        }
+    }
+
+    fn check(lyr: &str, frm: Option<&str>) -> Vec<Diagnostic> {
+        let mut config = Config::default();
+        // lines can be 100 pixels wide, 97 without the margin
+        config.display_width = 100;
+        config.text_style.outline_width = 0;
+        let mut diagnostics = Diagnostics { list: Vec::new() };
+        check_contents(Path::new("song.lyr"),
+                       lyr,
+                       Path::new("song.frm"),
+                       frm,
+                       &FixedWidth,
+                       &config,
+                       &mut diagnostics);
+        diagnostics.list
+    }
+
+    /// Severity, file, line and column of each diagnostic
+    fn positions(diagnostics: &[Diagnostic]) -> Vec<(Severity, String, usize, usize)> {
+        diagnostics.iter()
+                   .map(|d| (d.severity, d.file.display().to_string(), d.line, d.column))
+                   .collect()
+    }
+
+    fn position(severity: Severity,
+                file: &str,
+                line: usize,
+                column: usize)
+                -> (Severity, String, usize, usize) {
+        (severity, file.to_string(), line, column)
+    }
+
+    #[test]
+    fn valid_song() {
+        let diagnostics = check("%singer A top 00ff00 ff0000 Alice\n%color sung 0000ff\n&la&la\n",
+                                Some("0 10\n10 20\n"));
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn colors() {
+        let diagnostics = check("%singer A top 00ff00 red\n%color sung\n%color unsung #12345\n",
+                                None);
+        assert_eq!(positions(&diagnostics),
+                   vec![position(Severity::Warning, "song.lyr", 1, 22),
+                        position(Severity::Warning, "song.lyr", 2, 1),
+                        position(Severity::Warning, "song.lyr", 3, 15)]);
+        assert!(diagnostics[0].message.contains("'red'"));
+        assert!(diagnostics[1].message.contains("%color expects"));
+        assert!(diagnostics[2].message.contains("'#12345'"));
+    }
+
+    #[test]
+    fn widths() {
+        let diagnostics = check("&abcdefghi\n%title Song\n&abcde&fghij\n", None);
+        assert_eq!(positions(&diagnostics),
+                   vec![position(Severity::Warning, "song.lyr", 3, 2)]);
+        assert!(diagnostics[0].message.contains("103 pixels"));
+    }
+
+    #[test]
+    fn invalid_frame() {
+        let diagnostics = check("&la\n", Some("0 10\n\n10\n"));
+        assert_eq!(positions(&diagnostics),
+                   vec![position(Severity::Error, "song.frm", 3, 1)]);
+        assert!(diagnostics[0].message.contains("'10'"));
+    }
+
+    #[test]
+    fn missing_frames() {
+        let diagnostics = check("&la&li\n&lo\n", Some("0 10\n"));
+        assert_eq!(positions(&diagnostics),
+                   vec![position(Severity::Error, "song.lyr", 1, 5)]);
+        assert!(diagnostics[0].message.contains("'li'"));
+    }
+
+    #[test]
+    fn extra_frames() {
+        let diagnostics = check("&la\n", Some("0 10\n10 20\n20 30\n"));
+        assert_eq!(positions(&diagnostics),
+                   vec![position(Severity::Error, "song.frm", 2, 1)]);
+        assert!(diagnostics[0].message.contains("3 lines"));
+    }
+
+    #[test]
+    fn frames() {
+        let diagnostics = check("&a&b&c&d&e\n", Some("0 10\n10 10\n20 15\n5 30\n25 40\n"));
+        assert_eq!(positions(&diagnostics),
+                   vec![position(Severity::Warning, "song.frm", 2, 1),
+                        position(Severity::Error, "song.frm", 3, 1),
+                        position(Severity::Error, "song.frm", 4, 1),
+                        position(Severity::Warning, "song.frm", 5, 1)]);
+        assert!(diagnostics[0].message.contains("'b' (song.lyr:1:4) has a zero length"));
+        assert!(diagnostics[1].message.contains("ends at frame 15 before it begins"));
+        assert!(diagnostics[2].message.contains("before the previous syllable"));
+        assert!(diagnostics[3].message.contains("overlaps"));
+    }
+}
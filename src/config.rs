@@ -0,0 +1,194 @@
+use std::path::Path;
+
+use rustc_serialize::json::Json;
+
+use animation::LineTransitions;
+use background::BackgroundMode;
+use cards::CardTheme;
+use lyrics;
+use osd::OsdPosition;
+use style::TextStyle;
+use theme::Themes;
+
+/// Player configuration, read from a JSON file. Every key is optional and
+/// falls back to its default value.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Size of the lyrics font, relative to the width of the video
+    pub font_size: f32,
+    /// Width of the video lines are measured against by the check command,
+    /// the lyrics font scales with it
+    pub display_width: u32,
+    /// Minimal instrumental gap before a line for a countdown to be displayed,
+    /// in milliseconds
+    pub countdown_gap: u32,
+    /// How long before the line the countdown starts, in milliseconds
+    pub countdown_duration: u32,
+    /// Lyrics variant displayed under the sung one, "fr" for `<video>.fr.lyr`
+    pub secondary_lyrics: Option<String>,
+    /// Size of the secondary lyrics font, relative to the main one
+    pub secondary_font_scale: f32,
+    /// How lines appear before their first syllable and disappear after
+    /// their last one
+    pub transitions: LineTransitions,
+    /// Fill, outline, shadow and glow of the text
+    pub text_style: TextStyle,
+    /// Where the player state is displayed
+    pub osd_position: OsdPosition,
+    /// How long an OSD message stays on screen, in milliseconds
+    pub osd_timeout: u32,
+    /// Card displayed over the beginning of each song
+    pub title_card: CardTheme,
+    /// Card displayed between two songs of the queue
+    pub interlude: CardTheme,
+    /// What is displayed behind the lyrics of files without video
+    pub audio_background: BackgroundMode,
+    /// Directory of the images of the slideshow
+    pub background_images: Option<String>,
+    /// How long each image of the slideshow is displayed, in milliseconds
+    pub slideshow_interval: u32,
+    /// lavfi filter turning the audio into a video, see the ffmpeg
+    /// documentation of showcqt, showwaves or showspectrum
+    pub visualizer: String,
+    /// Draws the lyrics with OpenGL shaders instead of SDL surfaces
+    pub gl_text: bool,
+    /// Lets the lyrics use the black bars around the video, when they are
+    /// large enough, instead of covering it
+    pub lyrics_in_bars: bool,
+    /// Where screenshots are written, the current directory by default
+    pub screenshot_directory: Option<String>,
+    /// Index of the display a stage monitor window is opened on, for the
+    /// singers, none by default
+    pub stage_monitor: Option<i32>,
+    /// Colour themes cycled through, and the one used at start
+    pub themes: Themes,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            font_size: 0.04,
+            display_width: 960,
+            countdown_gap: 8000,
+            countdown_duration: 3000,
+            secondary_lyrics: None,
+            secondary_font_scale: 0.7,
+            transitions: LineTransitions::none(),
+            text_style: TextStyle::default(),
+            osd_position: OsdPosition::TopRight,
+            osd_timeout: 2000,
+            title_card: CardTheme {
+                duration: 5000,
+                font_scale: 1.2,
+                color: (255, 255, 255),
+                background: (0, 0, 0),
+                background_alpha: 140,
+            },
+            interlude: CardTheme {
+                duration: 10000,
+                font_scale: 1.2,
+                color: (255, 255, 255),
+                background: (0, 0, 0),
+                background_alpha: 255,
+            },
+            audio_background: BackgroundMode::Auto,
+            background_images: None,
+            slideshow_interval: 10000,
+            visualizer: "showcqt".to_string(),
+            gl_text: true,
+            lyrics_in_bars: false,
+            screenshot_directory: None,
+            stage_monitor: None,
+            themes: Themes::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let content = try!(lyrics::read_file(path).map_err(|e| e.to_string()));
+        let json = try!(Json::from_str(&content).map_err(|e| e.to_string()));
+        let mut config = Config::default();
+        config.apply(&json);
+        Ok(config)
+    }
+
+    fn apply(&mut self, json: &Json) {
+        if let Some(font_size) = json.find("font_size").and_then(|j| j.as_f64()) {
+            self.font_size = font_size as f32;
+        }
+        if let Some(display_width) = json.find("display_width").and_then(|j| j.as_u64()) {
+            self.display_width = display_width as u32;
+        }
+        if let Some(countdown_gap) = json.find("countdown_gap").and_then(|j| j.as_u64()) {
+            self.countdown_gap = countdown_gap as u32;
+        }
+        if let Some(countdown_duration) = json.find("countdown_duration")
+                                              .and_then(|j| j.as_u64()) {
+            self.countdown_duration = countdown_duration as u32;
+        }
+        if let Some(secondary_lyrics) = json.find("secondary_lyrics").and_then(|j| j.as_string()) {
+            self.secondary_lyrics = Some(secondary_lyrics.to_string());
+        }
+        if let Some(scale) = json.find("secondary_font_scale").and_then(|j| j.as_f64()) {
+            self.secondary_font_scale = scale as f32;
+        }
+        if let Some(appear) = json.find("appear_transition") {
+            self.transitions.appear.apply(appear);
+        }
+        if let Some(disappear) = json.find("disappear_transition") {
+            self.transitions.disappear.apply(disappear);
+        }
+        if let Some(text_style) = json.find("text_style") {
+            self.text_style.apply(text_style);
+        }
+        if let Some(osd_position) = json.find("osd_position")
+                                        .and_then(|j| j.as_string())
+                                        .and_then(OsdPosition::parse) {
+            self.osd_position = osd_position;
+        }
+        if let Some(osd_timeout) = json.find("osd_timeout").and_then(|j| j.as_u64()) {
+            self.osd_timeout = osd_timeout as u32;
+        }
+        if let Some(title_card) = json.find("title_card") {
+            self.title_card.apply(title_card);
+        }
+        if let Some(interlude) = json.find("interlude") {
+            self.interlude.apply(interlude);
+        }
+        if let Some(mode) = json.find("audio_background")
+                                .and_then(|j| j.as_string())
+                                .and_then(BackgroundMode::parse) {
+            self.audio_background = mode;
+        }
+        if let Some(images) = json.find("background_images").and_then(|j| j.as_string()) {
+            self.background_images = Some(images.to_string());
+        }
+        if let Some(interval) = json.find("slideshow_interval").and_then(|j| j.as_u64()) {
+            self.slideshow_interval = interval as u32;
+        }
+        if let Some(visualizer) = json.find("visualizer").and_then(|j| j.as_string()) {
+            self.visualizer = visualizer.to_string();
+        }
+        if let Some(gl_text) = json.find("gl_text").and_then(|j| j.as_boolean()) {
+            self.gl_text = gl_text;
+        }
+        if let Some(lyrics_in_bars) = json.find("lyrics_in_bars").and_then(|j| j.as_boolean()) {
+            self.lyrics_in_bars = lyrics_in_bars;
+        }
+        if let Some(directory) = json.find("screenshot_directory").and_then(|j| j.as_string()) {
+            self.screenshot_directory = Some(directory.to_string());
+        }
+        if let Some(display) = json.find("stage_monitor").and_then(|j| j.as_i64()) {
+            self.stage_monitor = Some(display as i32);
+        }
+        if let Some(themes) = json.find("themes") {
+            self.themes.apply(themes);
+        }
+        if let Some(theme) = json.find("theme").and_then(|j| j.as_string()) {
+            if !self.themes.select(theme) {
+                warn!("Unknown theme {}", theme);
+            }
+        }
+    }
+}
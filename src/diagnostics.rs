@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use mpv::Mpv;
+
+/// Weight of a new frame in the averages displayed by the overlay
+const SMOOTHING: f64 = 0.1;
+
+/// Timings of a frame, in milliseconds, and the state of the mpv output
+/// when it was displayed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sample {
+    /// Time since the previous frame was displayed
+    pub frame: f64,
+    /// Time spent drawing the video
+    pub mpv_draw: f64,
+    /// Time spent drawing the lyrics, cards and OSD over it
+    pub overlay: f64,
+    /// Time spent presenting the frame, waiting for vsync included
+    pub swap: f64,
+    pub dropped_frames: i64,
+    pub delayed_frames: i64,
+    /// Difference between the audio and video clocks
+    pub avsync: f64,
+    /// The loop slept until an event before this frame, nothing being
+    /// animated : `frame` includes the sleep
+    pub idle: bool,
+}
+
+/// Frame timing statistics, displayed over the video and written as CSV
+pub struct Diagnostics {
+    pub visible: bool,
+    average: Sample,
+    last_frame: Option<Instant>,
+    started: Instant,
+    csv: Option<BufWriter<File>>,
+}
+
+pub fn ms(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+}
+
+impl Diagnostics {
+    /// Writes a row per frame to `csv_path` if given
+    pub fn new(csv_path: Option<&Path>) -> io::Result<Diagnostics> {
+        let csv = match csv_path {
+            Some(path) => {
+                let mut csv = BufWriter::new(try!(File::create(path)));
+                try!(writeln!(csv,
+                              "elapsed_ms,position_ms,frame_ms,mpv_draw_ms,overlay_ms,swap_ms,\
+                               dropped_frames,delayed_frames,avsync_ms,idle"));
+                Some(csv)
+            }
+            None => None,
+        };
+        Ok(Diagnostics {
+            visible: false,
+            average: Sample::default(),
+            last_frame: None,
+            started: Instant::now(),
+            csv: csv,
+        })
+    }
+
+    /// Whether timings are measured at all
+    pub fn enabled(&self) -> bool {
+        self.visible || self.csv.is_some()
+    }
+
+    /// Records a frame presented just now, `position` being the playback
+    /// position in milliseconds. The frame time and the mpv counters are
+    /// filled in here.
+    pub fn record(&mut self, mpv: &Mpv, position: u32, mut sample: Sample) {
+        let now = Instant::now();
+        sample.frame = self.last_frame.map(|last| ms(now.duration_since(last))).unwrap_or(0.0);
+        self.last_frame = Some(now);
+        sample.dropped_frames = mpv.get_property::<i64>("frame-drop-count")
+                                   .or_else(|_| mpv.get_property::<i64>("drop-frame-count"))
+                                   .unwrap_or(0);
+        sample.delayed_frames = mpv.get_property::<i64>("vo-delayed-frame-count").unwrap_or(0);
+        sample.avsync = mpv.get_property::<f64>("avsync").unwrap_or(0.0) * 1000.0;
+
+        {
+            let average = &mut self.average;
+            if !sample.idle {
+                average.frame += (sample.frame - average.frame) * SMOOTHING;
+            }
+            average.mpv_draw += (sample.mpv_draw - average.mpv_draw) * SMOOTHING;
+            average.overlay += (sample.overlay - average.overlay) * SMOOTHING;
+            average.swap += (sample.swap - average.swap) * SMOOTHING;
+            average.avsync += (sample.avsync - average.avsync) * SMOOTHING;
+            average.dropped_frames = sample.dropped_frames;
+            average.delayed_frames = sample.delayed_frames;
+        }
+
+        let mut failed = false;
+        if let Some(ref mut csv) = self.csv {
+            failed = writeln!(csv,
+                              "{:.3},{},{:.3},{:.3},{:.3},{:.3},{},{},{:.3},{}",
+                              ms(self.started.elapsed()),
+                              position,
+                              sample.frame,
+                              sample.mpv_draw,
+                              sample.overlay,
+                              sample.swap,
+                              sample.dropped_frames,
+                              sample.delayed_frames,
+                              sample.avsync,
+                              sample.idle as u8)
+                         .map_err(|e| warn!("Failed to write diagnostics, stopping : {}", e))
+                         .is_err();
+        }
+        if failed {
+            self.csv = None;
+        }
+    }
+
+    /// Lines of the overlay, with averaged timings
+    pub fn lines(&self) -> Vec<String> {
+        let average = &self.average;
+        let fps = if average.frame > 0.0 {
+            1000.0 / average.frame
+        } else {
+            0.0
+        };
+        vec![format!("Frame {:.1} ms ({:.0} fps)", average.frame, fps),
+             format!("mpv draw {:.1} ms", average.mpv_draw),
+             format!("Overlay {:.1} ms", average.overlay),
+             format!("Swap {:.1} ms", average.swap),
+             format!("Dropped {} Delayed {}", average.dropped_frames, average.delayed_frames),
+             format!("A/V sync {:+.1} ms", average.avsync)]
+    }
+}
@@ -2,18 +2,33 @@ extern crate sdl2;
 extern crate sdl2_ttf;
 use sdl2::render::{Renderer, TextureQuery, BlendMode};
 use sdl2::rect::Rect;
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::surface::Surface;
 use std::vec::Vec;
+use std::cmp;
 use std::cmp::Ordering;
 use std::path::Path;
 use std::ops::Index;
 
 use std::ops::DerefMut;
 
+use animation::Appearance;
+use cards::CardTheme;
+use config::Config;
+use gltext::GlText;
+use layout::Area;
+use lyrics::{Line, Metadata, Region, Rgb, Song, Syllable};
+use monitor::StageView;
+use osd::{Osd, OsdPosition};
+use screenshot::Screenshot;
+use style::TextStyle;
+use theme::Theme;
+
 pub struct FontSet {
     font_size: u16,
     font_regular: sdl2_ttf::Font,
-    font_bold: sdl2_ttf::Font,
+    /// Fonts drawing outlines, with their outline width
+    fonts_outline: Vec<(u16, sdl2_ttf::Font)>,
 }
 
 impl Eq for FontSet {}
@@ -50,40 +65,70 @@ impl FontSet {
     pub fn get_regular_font(&self) -> &sdl2_ttf::Font {
         &self.font_regular
     }
-    pub fn get_outline_font(&self) -> &sdl2_ttf::Font {
-        &self.font_bold
+    /// Returns the font drawing outlines of `outline_width` pixels, the
+    /// regular font for a width of 0
+    pub fn get_outline_font(&self, outline_width: u16) -> Option<&sdl2_ttf::Font> {
+        if outline_width == 0 {
+            return Some(&self.font_regular);
+        }
+        self.fonts_outline
+            .iter()
+            .find(|&&(width, _)| width == outline_width)
+            .map(|&(_, ref font)| font)
     }
 }
 
-// MADE PUBLIC FOR TESTS, MAKE PRIVATE WHEN NOT NECESSARY ANYMORE
-struct FontList {
+pub const FONT_PATH: &'static str = "./res/DejaVuSansMono-Bold.ttf";
+
+pub struct FontList {
     // font list is a SORTED font list
     fonts: Vec<FontSet>,
 }
 
 impl FontList {
-    pub fn new(font_path: &Path, ttf_context: &sdl2_ttf::Sdl2TtfContext) -> Result<FontList, ()> {
+    /// Loads a size of the font every `font_size_increment` points, with an
+    /// outlined font for each of `outline_widths`
+    pub fn new(font_path: &Path,
+               ttf_context: &sdl2_ttf::Sdl2TtfContext,
+               outline_widths: &[u16],
+               font_size_increment: u16)
+               -> Result<FontList, ()> {
         let mut result = FontList { fonts: Vec::<FontSet>::new() };
         let mut font_size = 4;
         let font_size_max = 128;
-        let font_size_increment = 1;
         let mut error: bool = false;
         'fontlist: while (font_size < font_size_max) {
-            let mut font_bold;
+            let mut fonts_outline: Vec<(u16, sdl2_ttf::Font)> = Vec::new();
+            for &outline_width in outline_widths {
+                if outline_width == 0 ||
+                   fonts_outline.iter().any(|&(width, _)| width == outline_width) {
+                    continue;
+                }
+                match ttf_context.load_font(font_path, font_size) {
+                    Ok(mut font_outline) => {
+                        font_outline.set_outline_width(outline_width);
+                        fonts_outline.push((outline_width, font_outline));
+                    }
+                    Err(_) => {
+                        error = true;
+                        break 'fontlist;
+                    }
+                }
+            }
+            let font_regular;
             match ttf_context.load_font(font_path, font_size) {
-                Ok(font_set) => {
-                    font_bold = font_set;
+                Ok(font) => {
+                    font_regular = font;
                 }
                 Err(_) => {
                     error = true;
                     break 'fontlist;
                 }
             }
-            font_bold.set_outline_width(2);
             result.fonts.push(FontSet {
                 font_size: font_size,
-                font_regular: ttf_context.load_font(font_path, font_size).unwrap(),
-                font_bold: font_bold,
+                font_regular: font_regular,
+                fonts_outline: fonts_outline,
             });
             font_size += font_size_increment;
         }
@@ -142,55 +187,584 @@ pub struct Displayer<'a> {
     fonts: FontList,
     renderer: Renderer<'a>,
     ttf_context: sdl2_ttf::Sdl2TtfContext,
+    font_size: f32,
+    secondary_font_scale: f32,
+    style: TextStyle,
+    osd: Osd,
+    /// Draws the lyrics when OpenGL text rendering is available
+    gl_text: Option<GlText>,
+    /// Where the video is drawn in the window, None for the whole window
+    video_area: Option<Area>,
+    /// Lyrics may go in the black bars around the video
+    lyrics_in_bars: bool,
+    theme: Theme,
 }
 
+/// Opacity of fully visible text
+const TEXT_ALPHA: f32 = 128.0;
+
+/// Size of the current and next lines of the stage monitor, relative to the
+/// lyrics font
+const STAGE_LINE_SCALE: f32 = 1.5;
+
 impl<'a> Displayer<'a> {
-    pub fn new(mut renderer: Renderer<'a>) -> Result<Displayer<'a>, ()> {
+    pub fn new(mut renderer: Renderer<'a>, config: &Config) -> Result<Displayer<'a>, ()> {
         renderer.set_blend_mode(BlendMode::Blend);
         let ttf_context = sdl2_ttf::init().unwrap();
-        let font_list = FontList::new(Path::new("./res/DejaVuSansMono-Bold.ttf"),
-                                      &ttf_context)
+        // software renderers have no OpenGL context
+        let gl_text = if config.gl_text && is_opengl(&renderer) {
+            GlText::new(Path::new(FONT_PATH), &ttf_context, config.text_style)
+                .map_err(|e| warn!("OpenGL text rendering unavailable : {}", e))
+                .ok()
+        } else {
+            None
+        };
+        // with OpenGL, SDL_ttf only draws the OSD and the cards, which don't
+        // need every size
+        let font_size_increment = if gl_text.is_some() { 4 } else { 1 };
+        let font_list = FontList::new(Path::new(FONT_PATH),
+                                      &ttf_context,
+                                      &config.text_style.outline_widths(),
+                                      font_size_increment)
                             .unwrap();
-        let displayer = Displayer {
+        let mut displayer = Displayer {
             fonts: font_list,
             ttf_context: ttf_context,
             renderer: renderer,
+            font_size: config.font_size,
+            secondary_font_scale: config.secondary_font_scale,
+            style: config.text_style,
+            osd: Osd::new(config.osd_position, config.osd_timeout),
+            gl_text: gl_text,
+            video_area: None,
+            lyrics_in_bars: config.lyrics_in_bars,
+            theme: Theme::default_theme(),
         };
+        displayer.set_theme(config.themes.current(&Metadata::default()));
         Ok(displayer)
     }
 
-    pub fn display(&mut self, text: &str) {
-        let size: f32 = 0.04;
-        let window_width = self.renderer.window().unwrap().size().0 as f32;
-        let font_set = self.fonts.get_closest_font_set((size * window_width) as u16).unwrap();
-        let font = font_set.get_regular_font();
-        let font_outline = font_set.get_outline_font();
-        let surface = font.render(text)
-                          .blended(Color::RGB(180, 180, 180))
-                          .unwrap();
-        let mut surface_outline = font_outline.render(text)
-                                              .blended(Color::RGB(0, 0, 0))
-                                              .unwrap();
-        let outline_width: u32 = 2;
-        let (width, height) = surface_outline.size();
-
-        surface.blit(None,
-                     surface_outline.deref_mut(),
-                     Some(Rect::new(outline_width as i32,
-                                    outline_width as i32,
-                                    (width - outline_width),
-                                    (height - outline_width))));
-        let mut texture = self.renderer.create_texture_from_surface(&surface_outline).unwrap();
+    /// Displays a lyrics line in the region of its singers, the `sung` first
+    /// syllables are highlighted, the last of them up to `progress`, between
+    /// 0 and 1. `row` moves the line down by as many lines, to display a
+    /// transliteration under the original line for instance.
+    pub fn display_line(&mut self,
+                        song: &Song,
+                        line: &Line,
+                        sung: usize,
+                        progress: f32,
+                        row: i32,
+                        appearance: &Appearance) {
+        let line_height = self.line_height(line);
+        let y = self.line_y(song, line) + row * line_height +
+                (appearance.offset_y * line_height as f32) as i32;
+        let font_size = self.lyrics_font_size() * appearance.scale;
+        let alpha = (TEXT_ALPHA * appearance.alpha) as u8;
+        let x = self.lyrics_area().x + 3;
+        self.display_syllables(song, &line.syllables, sung, progress, x, y, font_size, alpha);
+    }
+
+    /// Displays a line of a secondary variant, a translation for instance,
+    /// under `line` in a smaller font. `row` counts in lines of the primary
+    /// variant, like in `display_line`.
+    pub fn display_secondary_line(&mut self,
+                                  song: &Song,
+                                  line: &Line,
+                                  secondary_song: &Song,
+                                  secondary_line: &Line,
+                                  sung: usize,
+                                  row: i32,
+                                  appearance: &Appearance) {
+        let line_height = self.line_height(line);
+        let y = self.line_y(song, line) + row * line_height +
+                (appearance.offset_y * line_height as f32) as i32;
+        let font_size = self.lyrics_font_size() * self.secondary_font_scale * appearance.scale;
+        let alpha = (TEXT_ALPHA * appearance.alpha) as u8;
+        let x = self.lyrics_area().x + 3;
+        self.display_syllables(secondary_song,
+                               &secondary_line.syllables,
+                               sung,
+                               1.0,
+                               x,
+                               y,
+                               font_size,
+                               alpha);
+    }
+
+    fn display_syllables(&mut self,
+                         song: &Song,
+                         syllables: &[Syllable],
+                         sung: usize,
+                         progress: f32,
+                         x: i32,
+                         y: i32,
+                         font_size: f32,
+                         alpha: u8) {
+        if self.gl_text.is_some() {
+            self.push_syllables(song, syllables, sung, progress, x, y, font_size, alpha);
+            return;
+        }
+        let font_size = font_size as u16;
+        let mut x = x;
+        for (index, syllable) in syllables.iter().enumerate() {
+            if syllable.text.is_empty() {
+                continue;
+            }
+            let (sung_color, unsung_color) = self.theme.syllable_colors(song, syllable);
+            let (r, g, b) = if index + 1 == sung {
+                self.theme.active.unwrap_or(sung_color)
+            } else if index < sung {
+                sung_color
+            } else {
+                unsung_color
+            };
+            self.display_text_at(&syllable.text, Color::RGB(r, g, b), x, y, font_size, alpha);
+            x += self.text_size_at(&syllable.text, font_size).0 as i32;
+        }
+    }
+
+    /// Adds syllables to the batch of the OpenGL text renderer, which wipes
+    /// the sung color over the syllable being sung
+    fn push_syllables(&mut self,
+                      song: &Song,
+                      syllables: &[Syllable],
+                      sung: usize,
+                      progress: f32,
+                      x: i32,
+                      y: i32,
+                      font_size: f32,
+                      alpha: u8) {
+        let outline_width = self.style.outline_width as f32;
+        let theme = &self.theme;
+        let gl_text = self.gl_text.as_mut().unwrap();
+        let mut x = x as f32;
+        for (index, syllable) in syllables.iter().enumerate() {
+            if syllable.text.is_empty() {
+                continue;
+            }
+            let width = gl_text.text_width(&syllable.text, font_size);
+            let wipe = if index + 1 < sung {
+                1e9
+            } else if index + 1 == sung {
+                x + outline_width + width * progress.max(0.0).min(1.0)
+            } else {
+                -1e9
+            };
+            let (sung_color, (ur, ug, ub)) = theme.syllable_colors(song, syllable);
+            let (sr, sg, sb) = if index + 1 == sung {
+                theme.active.unwrap_or(sung_color)
+            } else {
+                sung_color
+            };
+            x += gl_text.push_text(&syllable.text,
+                                   x,
+                                   y as f32,
+                                   font_size,
+                                   Color::RGBA(sr, sg, sb, alpha),
+                                   Color::RGBA(ur, ug, ub, alpha),
+                                   wipe);
+        }
+    }
+
+    /// Draws the lyrics batched for the OpenGL text renderer, before anything
+    /// is drawn over them
+    fn flush_text(&mut self) {
+        if let Some(ref mut gl_text) = self.gl_text {
+            let (width, height) = self.renderer.output_size().unwrap();
+            gl_text.flush(width, height);
+        }
+    }
+
+    /// Displays a bar under `line` whose length is the `remaining` part of the
+    /// countdown before the line begins
+    pub fn display_countdown(&mut self, song: &Song, line: &Line, remaining: f32) {
+        self.flush_text();
+        let lyrics_font_size = self.lyrics_font_size() as u16;
+        let line_width = self.text_size_at(&line.text(), lyrics_font_size).0;
+        let line_height = self.line_height(line) as u32;
+        let y = self.line_y(song, line) + line_height as i32;
+        let width = (remaining * line_width as f32) as u32;
+        if width == 0 {
+            return;
+        }
+        let (r, g, b) = self.theme.syllable_colors(song, &line.syllables[0]).0;
+        self.renderer.set_draw_color(Color::RGBA(r, g, b, 200));
+        let x = self.lyrics_area().x + 3;
+        self.renderer.fill_rect(Rect::new(x, y, width, cmp::max(line_height / 8, 2)));
+    }
+
+    /// Draws the stage monitor : how long before the next line, the line
+    /// being sung, then the upcoming lines, the first one large and the
+    /// others in the lyrics font. Every line is centered, on a black screen.
+    pub fn display_stage(&mut self, song: &Song, view: &StageView) {
+        self.flush_text();
+        let large = self.lyrics_font_size() * STAGE_LINE_SCALE;
+        let small = self.lyrics_font_size();
+        let mut y = 3;
+        if let Some(next_entry) = view.next_entry {
+            let text = format!("Next line in {}", (next_entry + 999) / 1000);
+            let fill = self.osd_color();
+            let (width, height) = self.text_size_at(&text, large as u16);
+            let x = (self.output_size().0 as i32 - width as i32) / 2;
+            self.display_text_at(&text, fill, x, y, large as u16, 255);
+            y += height as i32;
+        }
+        if let Some((line, sung, progress)) = view.current {
+            y += self.display_centered_line(song, line, sung, progress, y, large);
+        }
+        for (index, line) in view.upcoming.iter().enumerate() {
+            let font_size = if index == 0 && view.current.is_some() {
+                large
+            } else {
+                small
+            };
+            y += self.display_centered_line(song, line, 0, 0.0, y, font_size);
+        }
+    }
+
+    /// Displays lines at the bottom left corner of the stage monitor
+    pub fn display_stage_footer(&mut self, lines: &[String]) {
+        self.flush_text();
+        let window_height = self.output_size().1 as i32;
+        let sizes: Vec<(u32, u32)> = lines.iter().map(|line| self.text_size(line)).collect();
+        let block_height = sizes.iter().fold(0, |height, &(_, line_height)| height + line_height);
+        let mut y = window_height - block_height as i32 - 3;
+        let fill = self.osd_color();
+        for (line, &(_, line_height)) in lines.iter().zip(sizes.iter()) {
+            let font_size = self.font_size();
+            self.display_text_at(line, fill, 3, y, font_size, 255);
+            y += line_height as i32;
+        }
+    }
+
+    /// Displays a fully opaque line centered horizontally, returns its height
+    fn display_centered_line(&mut self,
+                             song: &Song,
+                             line: &Line,
+                             sung: usize,
+                             progress: f32,
+                             y: i32,
+                             font_size: f32)
+                             -> i32 {
+        let (width, height) = if self.gl_text.is_some() {
+            let gl_text = self.gl_text.as_mut().unwrap();
+            let width = line.syllables
+                            .iter()
+                            .fold(0.0, |width, s| width + gl_text.text_width(&s.text, font_size));
+            (width as i32, gl_text.line_height(font_size) as i32)
+        } else {
+            let (width, height) = self.text_size_at(&line.text(), font_size as u16);
+            (width as i32, height as i32)
+        };
+        let x = (self.output_size().0 as i32 - width) / 2;
+        self.display_syllables(song, &line.syllables, sung, progress, x, y, font_size, 255);
+        height
+    }
+
+    /// Shows a short message on the OSD, lines are separated by `\n`
+    pub fn notify(&mut self, text: String) {
+        self.osd.show(text);
+    }
+
+    /// Milliseconds before the OSD message disappears
+    pub fn osd_remaining(&self) -> Option<u32> {
+        self.osd.remaining()
+    }
+
+    /// Displays the OSD message, if it has not timed out
+    pub fn display_osd(&mut self) {
+        self.flush_text();
+        let lines: Vec<String> = match self.osd.message() {
+            Some(text) => text.lines().map(|line| line.to_string()).collect(),
+            None => return,
+        };
+        let (window_width, window_height) = self.output_size();
+        let sizes: Vec<(u32, u32)> = lines.iter().map(|line| self.text_size(line)).collect();
+        let block_height = sizes.iter().fold(0, |height, &(_, line_height)| height + line_height);
+        let mut y = match self.osd.position {
+            OsdPosition::TopLeft | OsdPosition::TopRight => 3,
+            OsdPosition::Center => (window_height as i32 - block_height as i32) / 2,
+            OsdPosition::BottomLeft | OsdPosition::BottomRight => {
+                window_height as i32 - block_height as i32 - 3
+            }
+        };
+        let fill = self.osd_color();
+        for (line, &(line_width, line_height)) in lines.iter().zip(sizes.iter()) {
+            let x = match self.osd.position {
+                OsdPosition::TopLeft | OsdPosition::BottomLeft => 3,
+                OsdPosition::Center => (window_width as i32 - line_width as i32) / 2,
+                OsdPosition::TopRight | OsdPosition::BottomRight => {
+                    window_width as i32 - line_width as i32 - 3
+                }
+            };
+            self.display_text(line, fill, x, y);
+            y += line_height as i32;
+        }
+    }
+
+    /// Displays the diagnostics overlay in the top left corner, over a dark
+    /// background keeping it readable on any video
+    pub fn display_diagnostics(&mut self, lines: &[String]) {
+        self.flush_text();
+        let sizes: Vec<(u32, u32)> = lines.iter().map(|line| self.text_size(line)).collect();
+        let block_width = sizes.iter().map(|&(line_width, _)| line_width).max().unwrap_or(0);
+        let block_height = sizes.iter().fold(0, |height, &(_, line_height)| height + line_height);
+        self.renderer.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        self.renderer.fill_rect(Rect::new(0, 0, block_width + 6, block_height + 6));
+        let fill = self.osd_color();
+        let mut y = 3;
+        for (line, &(_, line_height)) in lines.iter().zip(sizes.iter()) {
+            self.display_text(line, fill, 3, y);
+            y += line_height as i32;
+        }
+    }
+
+    /// Displays centered lines over a background covering the whole window
+    pub fn display_card(&mut self, lines: &[String], theme: &CardTheme) {
+        self.flush_text();
+        let (window_width, window_height) = self.output_size();
+        if theme.background_alpha > 0 {
+            let (r, g, b) = theme.background;
+            self.renderer.set_draw_color(Color::RGBA(r, g, b, theme.background_alpha));
+            self.renderer.fill_rect(Rect::new(0, 0, window_width, window_height));
+        }
+        let font_size = (self.font_size() as f32 * theme.font_scale) as u16;
+        let sizes: Vec<(u32, u32)> = lines.iter()
+                                          .map(|line| self.text_size_at(line, font_size))
+                                          .collect();
+        let block_height = sizes.iter().fold(0, |height, &(_, line_height)| height + line_height);
+        let mut y = (window_height as i32 - block_height as i32) / 2;
+        for (line, &(line_width, line_height)) in lines.iter().zip(sizes.iter()) {
+            let x = (window_width as i32 - line_width as i32) / 2;
+            self.display_text_at(line, rgb(theme.color), x, y, font_size, TEXT_ALPHA as u8);
+            y += line_height as i32;
+        }
+    }
+
+    /// Changes the colours of the lyrics and messages
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        let style = self.themed_style();
+        if let Some(ref mut gl_text) = self.gl_text {
+            gl_text.set_style(style);
+        }
+    }
+
+    /// Darkens the video under the lyrics as much as the theme asks
+    pub fn dim_background(&mut self) {
+        if self.theme.background_dim == 0 {
+            return;
+        }
+        let (width, height) = self.output_size();
+        self.renderer.set_draw_color(Color::RGBA(0, 0, 0, self.theme.background_dim));
+        self.renderer.fill_rect(Rect::new(0, 0, width, height));
+    }
+
+    /// Text style with the colours of the theme
+    fn themed_style(&self) -> TextStyle {
+        let mut style = self.style;
+        if let Some(outline) = self.theme.outline {
+            style.outline_color = outline;
+        }
+        style
+    }
+
+    /// Colour of the OSD and of the other player messages
+    fn osd_color(&self) -> Color {
+        rgb(self.theme.osd.unwrap_or(self.style.fill))
+    }
+
+    /// Tells where the video is drawn, the lyrics are laid out over it
+    pub fn set_video_area(&mut self, video_area: Option<Area>) {
+        self.video_area = video_area;
+    }
+
+    /// Area the lyrics are laid out in : the video, or the whole window
+    /// width if they may go in the bars
+    fn lyrics_area(&self) -> Area {
+        let window = Area::window(self.output_size());
+        match self.video_area {
+            Some(video) if self.lyrics_in_bars => {
+                Area {
+                    x: window.x,
+                    width: window.width,
+                    ..video
+                }
+            }
+            Some(video) => video,
+            None => window,
+        }
+    }
+
+    fn line_y(&self, song: &Song, line: &Line) -> i32 {
+        let window_height = self.output_size().1 as i32;
+        let area = self.lyrics_area();
+        let line_height = self.line_height(line);
+        match song.line_region(line) {
+            Region::Top if self.lyrics_in_bars && area.y >= line_height + 6 => {
+                area.y - line_height - 3
+            }
+            Region::Top => area.y + 3,
+            Region::Middle => area.y + (area.height as i32 - line_height) / 2,
+            Region::Bottom if self.lyrics_in_bars &&
+                              window_height - area.bottom() >= 2 * line_height + 6 => {
+                area.bottom() + 3
+            }
+            // the last row of the area is kept for the offset
+            Region::Bottom => area.bottom() - 2 * line_height - 6,
+        }
+    }
+
+    /// Displays the current lyrics offset in the bottom left corner of the
+    /// lyrics area, in the row kept for it under the bottom lines
+    pub fn display_offset(&mut self, offset: i32) {
+        self.flush_text();
+        let text = format!("Offset {:+} ms", offset);
+        let area = self.lyrics_area();
+        let text_height = self.text_size(&text).1 as i32;
+        let fill = self.osd_color();
+        self.display_text(&text, fill, area.x + 3, area.bottom() - text_height - 3);
+    }
+
+    /// Size of the lyrics font in pixels, not rounded since the OpenGL text
+    /// renderer draws any size
+    fn lyrics_font_size(&self) -> f32 {
+        self.font_size * self.lyrics_area().width as f32
+    }
+
+    fn line_height(&self, line: &Line) -> i32 {
+        match self.gl_text {
+            Some(ref gl_text) => gl_text.line_height(self.lyrics_font_size()) as i32,
+            None => self.text_size_at(&line.text(), self.lyrics_font_size() as u16).1 as i32,
+        }
+    }
+
+    fn font_size(&self) -> u16 {
+        let window_width = self.output_size().0 as f32;
+        (self.font_size * window_width) as u16
+    }
+
+    fn text_size(&self, text: &str) -> (u32, u32) {
+        self.text_size_at(text, self.font_size())
+    }
+
+    fn text_size_at(&self, text: &str, font_size: u16) -> (u32, u32) {
+        let font_set = self.fonts.get_closest_font_set(font_size).unwrap();
+        font_set.get_regular_font().size_of(text).unwrap()
+    }
+
+    fn display_text(&mut self, text: &str, color: Color, x: i32, y: i32) {
+        let font_size = self.font_size();
+        self.display_text_at(text, color, x, y, font_size, TEXT_ALPHA as u8);
+    }
+
+    fn display_text_at(&mut self,
+                       text: &str,
+                       color: Color,
+                       x: i32,
+                       y: i32,
+                       font_size: u16,
+                       alpha: u8) {
+        // SDL_ttf fails on text of zero width
+        if alpha == 0 || text.is_empty() {
+            return;
+        }
+        let style = self.themed_style();
+        let font_set = self.fonts.get_closest_font_set(font_size).unwrap();
+        let mut fill = font_set.get_regular_font()
+                               .render(text)
+                               .blended(color)
+                               .unwrap();
+        if style.gradient != 1.0 {
+            apply_gradient(&mut fill, style.gradient);
+        }
+        // every layer is placed relatively to the top left corner of the fill,
+        // `margin` pixels away from the corner of the canvas
+        let outline_width = style.outline_width as i32;
+        let padding = style.padding();
+        let margin = padding as i32 + outline_width;
+        let (fill_width, fill_height) = fill.size();
+        let mut canvas = Surface::new(fill_width + 2 * margin as u32,
+                                      fill_height + 2 * margin as u32,
+                                      PixelFormatEnum::ARGB8888)
+                             .unwrap();
+        if let Some(glow) = style.glow {
+            let glow_width = style.outline_width + glow.radius as u16;
+            let layer = font_set.get_outline_font(glow_width)
+                                .unwrap()
+                                .render(text)
+                                .blended(rgb(glow.color))
+                                .unwrap();
+            let layer = blurred(&layer, glow.radius, glow.color);
+            let offset = margin - glow_width as i32 - glow.radius as i32;
+            blit_at(&layer, &mut canvas, offset, offset);
+        }
+        let font_outline = font_set.get_outline_font(style.outline_width).unwrap();
+        if let Some(shadow) = style.shadow {
+            let mut layer = font_outline.render(text)
+                                        .blended(rgb(shadow.color))
+                                        .unwrap();
+            layer.set_alpha_mod(shadow.alpha);
+            let layer = blurred(&layer, shadow.blur, shadow.color);
+            let offset = margin - outline_width - shadow.blur as i32;
+            blit_at(&layer,
+                    &mut canvas,
+                    offset + shadow.offset.0,
+                    offset + shadow.offset.1);
+        }
+        if outline_width > 0 {
+            let layer = font_outline.render(text)
+                                    .blended(rgb(style.outline_color))
+                                    .unwrap();
+            blit_at(&layer, &mut canvas, margin - outline_width, margin - outline_width);
+        }
+        blit_at(&fill, &mut canvas, margin, margin);
+
+        let mut texture = self.renderer.create_texture_from_surface(&canvas).unwrap();
         texture.set_blend_mode(BlendMode::Blend);
-        texture.set_alpha_mod(128);
+        texture.set_alpha_mod(alpha);
         let TextureQuery { width:texture_width, height:texture_height, .. } = texture.query();
+        // the outline starts at (x, y), whatever the padding
         self.renderer.copy(&mut texture,
                            None,
-                           Some(Rect::new(3, 3, texture_width, texture_height)));
+                           Some(Rect::new(x - padding as i32,
+                                          y - padding as i32,
+                                          texture_width,
+                                          texture_height)));
+    }
+
+    /// Reads back everything drawn so far, the video included, before the
+    /// frame is presented
+    pub fn screenshot(&mut self) -> Result<Screenshot, String> {
+        self.flush_text();
+        let (width, height) = self.output_size();
+        let pixels = try!(self.renderer.read_pixels(None, PixelFormatEnum::ARGB8888));
+        Ok(Screenshot {
+            width: width,
+            height: height,
+            pixels: pixels,
+        })
+    }
+
+    /// Fills the frame with black, for renderers mpv does not draw in
+    pub fn clear(&mut self) {
+        self.renderer.set_draw_color(Color::RGB(0, 0, 0));
+        self.renderer.clear();
     }
 
     pub fn render(&mut self) {
-        self.sdl_renderer_mut().window().unwrap().gl_swap_window();
+        self.flush_text();
+        // mpv draws with OpenGL behind the back of the renderer
+        if is_opengl(&self.renderer) {
+            self.renderer.window().unwrap().gl_swap_window();
+        } else {
+            self.renderer.present();
+        }
+    }
+
+    /// Size of the window, or of the surface of a software renderer
+    fn output_size(&self) -> (u32, u32) {
+        self.renderer.output_size().unwrap()
     }
 
     pub fn sdl_renderer_mut(&mut self) -> &mut Renderer<'a> {
@@ -201,3 +775,97 @@ impl<'a> Displayer<'a> {
         &self.renderer
     }
 }
+
+fn is_opengl(renderer: &Renderer) -> bool {
+    renderer.info().name == "opengl"
+}
+
+fn rgb((r, g, b): Rgb) -> Color {
+    Color::RGB(r, g, b)
+}
+
+/// Positions of the alpha, red, green and blue bytes of an ARGB8888 pixel,
+/// the format surfaces are rendered in by SDL_ttf
+#[cfg(target_endian = "little")]
+pub const ARGB_BYTES: [usize; 4] = [3, 2, 1, 0];
+#[cfg(target_endian = "big")]
+pub const ARGB_BYTES: [usize; 4] = [0, 1, 2, 3];
+
+fn blit_at(layer: &Surface, canvas: &mut Surface, x: i32, y: i32) {
+    let (width, height) = layer.size();
+    layer.blit(None, canvas.deref_mut(), Some(Rect::new(x, y, width, height))).unwrap();
+}
+
+/// Darkens or lightens the surface from its top to its bottom, where colours
+/// are multiplied by `bottom`
+fn apply_gradient(surface: &mut Surface, bottom: f32) {
+    let (width, height) = (surface.width() as usize, surface.height() as usize);
+    let pitch = surface.pitch() as usize;
+    surface.with_lock_mut(|pixels: &mut [u8]| {
+        for row in 0..height {
+            let factor = 1.0 + (bottom - 1.0) * row as f32 / cmp::max(height - 1, 1) as f32;
+            for column in 0..width {
+                let pixel = row * pitch + column * 4;
+                for &byte in &ARGB_BYTES[1..] {
+                    let value = pixels[pixel + byte] as f32 * factor;
+                    pixels[pixel + byte] = value.max(0.0).min(255.0) as u8;
+                }
+            }
+        }
+    });
+}
+
+/// Copies a layer drawn in a single `color` with `radius` transparent pixels
+/// around it, and blurs it by a box blur of that radius
+fn blurred(layer: &Surface, radius: u32, color: Rgb) -> Surface<'static> {
+    let (width, height) = layer.size();
+    let mut result = Surface::new(width + 2 * radius,
+                                  height + 2 * radius,
+                                  PixelFormatEnum::ARGB8888)
+                         .unwrap();
+    blit_at(layer, &mut result, radius as i32, radius as i32);
+    let (width, height) = (result.width() as usize, result.height() as usize);
+    let pitch = result.pitch() as usize;
+    let radius = radius as usize;
+    result.with_lock_mut(|pixels: &mut [u8]| {
+        // blending with the transparent canvas darkened the edges
+        for row in 0..height {
+            for column in 0..width {
+                let pixel = row * pitch + column * 4;
+                pixels[pixel + ARGB_BYTES[1]] = color.0;
+                pixels[pixel + ARGB_BYTES[2]] = color.1;
+                pixels[pixel + ARGB_BYTES[3]] = color.2;
+            }
+        }
+        if radius > 0 {
+            blur_alpha(pixels, height, width, 4, pitch, radius);
+            blur_alpha(pixels, width, height, pitch, 4, radius);
+        }
+    });
+    result
+}
+
+/// Box blur of the alpha channel in one direction, over `count` lines of
+/// `length` pixels. Pixels of a line are `stride` bytes apart, and lines
+/// `line_stride` bytes apart.
+fn blur_alpha(pixels: &mut [u8],
+              count: usize,
+              length: usize,
+              stride: usize,
+              line_stride: usize,
+              radius: usize) {
+    let window = (2 * radius + 1) as u32;
+    let mut line = vec![0u32; length];
+    for index in 0..count {
+        let start = index * line_stride + ARGB_BYTES[0];
+        for position in 0..length {
+            line[position] = pixels[start + position * stride] as u32;
+        }
+        for position in 0..length {
+            let first = position.saturating_sub(radius);
+            let last = cmp::min(position + radius + 1, length);
+            let sum = line[first..last].iter().fold(0, |sum, alpha| sum + alpha);
+            pixels[start + position * stride] = (sum / window) as u8;
+        }
+    }
+}
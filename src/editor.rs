@@ -0,0 +1,100 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use lyrics::{Line, Song};
+
+/// Records syllable timings from key presses : pressing the timing key marks
+/// the beginning of the current syllable, releasing it marks its end.
+pub struct Editor {
+    song: Song,
+    frm_path: PathBuf,
+    // (line, syllable) of the syllable being timed
+    cursor: (usize, usize),
+    pressed: bool,
+    // timings changed since they were loaded or saved
+    modified: bool,
+}
+
+impl Editor {
+    pub fn new(song: Song, frm_path: &Path) -> Editor {
+        Editor {
+            song: song,
+            frm_path: frm_path.to_path_buf(),
+            cursor: (0, 0),
+            pressed: false,
+            modified: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor.0 >= self.song.lines.len()
+    }
+
+    pub fn key_down(&mut self, time: u32) {
+        if self.is_finished() {
+            return;
+        }
+        let (line, syllable) = self.cursor;
+        let syllable = &mut self.song.lines[line].syllables[syllable];
+        syllable.begin = time;
+        syllable.end = time;
+        self.pressed = true;
+        self.modified = true;
+    }
+
+    pub fn key_up(&mut self, time: u32) {
+        if self.is_finished() || !self.pressed {
+            return;
+        }
+        {
+            let (line, syllable) = self.cursor;
+            let syllable = &mut self.song.lines[line].syllables[syllable];
+            syllable.end = ::std::cmp::max(time, syllable.begin);
+        }
+        self.pressed = false;
+        self.next();
+    }
+
+    /// Goes back one syllable so it can be timed again
+    pub fn back(&mut self) {
+        self.pressed = false;
+        let (line, syllable) = self.cursor;
+        if syllable > 0 {
+            self.cursor = (line, syllable - 1);
+        } else if line > 0 {
+            let previous_line = &self.song.lines[line - 1];
+            self.cursor = (line - 1, previous_line.syllables.len() - 1);
+        }
+    }
+
+    fn next(&mut self) {
+        let (line, syllable) = self.cursor;
+        if syllable + 1 < self.song.lines[line].syllables.len() {
+            self.cursor = (line, syllable + 1);
+        } else {
+            self.cursor = (line + 1, 0);
+        }
+    }
+
+    pub fn song(&self) -> &Song {
+        &self.song
+    }
+
+    /// Returns the line being timed and how many of its syllables are already timed
+    pub fn current_line(&self) -> Option<(&Line, usize)> {
+        self.song.lines.get(self.cursor.0).map(|line| (line, self.cursor.1))
+    }
+
+    /// Whether there are timings which haven't been saved
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Writes the timings as frame numbers of a `fps` video
+    pub fn save(&mut self, fps: f64) -> io::Result<()> {
+        info!("Writing timings to {} at {} fps", self.frm_path.display(), fps);
+        try!(self.song.write_frm(&self.frm_path, fps));
+        self.modified = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,629 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::mem;
+use std::path::Path;
+use std::ptr;
+
+use gl;
+use gl::types::*;
+use sdl2::pixels::Color;
+use sdl2_ttf;
+use sdl2_ttf::Font;
+
+use displayer::ARGB_BYTES;
+use lyrics::Rgb;
+use style::TextStyle;
+
+/// Width and height of the glyph atlas texture
+const ATLAS_SIZE: u32 = 2048;
+
+/// Size the glyphs are rasterized at before being turned into distance
+/// fields, text of any size is drawn from them
+const SDF_FONT_SIZE: u16 = 48;
+
+/// Distance, in pixels of the rasterized glyphs, covered by the distance
+/// field on each side of the edges. It bounds the width of the outline, the
+/// glow and the shadow.
+const SDF_SPREAD: i32 = 10;
+
+/// Floats per vertex : position, texture coordinates, sung and unsung colors,
+/// the wipe position and the shade of the gradient
+const VERTEX_SIZE: usize = 2 + 2 + 4 + 4 + 1 + 1;
+
+const VERTEX_SHADER: &'static str = "
+#version 120
+uniform vec2 screen;
+attribute vec2 position;
+attribute vec2 texcoord;
+attribute vec4 sung;
+attribute vec4 unsung;
+attribute float wipe;
+attribute float shade;
+varying vec2 v_texcoord;
+varying vec4 v_sung;
+varying vec4 v_unsung;
+varying float v_wipe;
+varying float v_shade;
+varying float v_x;
+void main() {
+    gl_Position = vec4(position.x / screen.x * 2.0 - 1.0,
+                       1.0 - position.y / screen.y * 2.0,
+                       0.0,
+                       1.0);
+    v_texcoord = texcoord;
+    v_sung = sung;
+    v_unsung = unsung;
+    v_wipe = wipe;
+    v_shade = shade;
+    v_x = position.x;
+}
+";
+
+/// The red channel of the atlas holds the distance to the edge of the glyph,
+/// 0.5 on the edge and more inside. `fwidth` gives how much it changes per
+/// pixel of the screen, which turns the widths in pixels of the style into
+/// distances whatever the size of the text.
+const FRAGMENT_SHADER: &'static str = "
+#version 120
+uniform sampler2D atlas;
+uniform vec4 outline_color;
+uniform float outline_width;
+uniform vec4 glow_color;
+uniform float glow_radius;
+uniform vec4 shadow_color;
+uniform vec2 shadow_offset;
+uniform float shadow_blur;
+varying vec2 v_texcoord;
+varying vec4 v_sung;
+varying vec4 v_unsung;
+varying float v_wipe;
+varying float v_shade;
+varying float v_x;
+
+vec4 over(vec4 top, vec4 bottom) {
+    float alpha = top.a + bottom.a * (1.0 - top.a);
+    if (alpha <= 0.0) {
+        return vec4(0.0);
+    }
+    return vec4((top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a)) / alpha, alpha);
+}
+
+void main() {
+    float distance = texture2D(atlas, v_texcoord).r;
+    float pixel = max(fwidth(distance), 0.0001);
+    float smoothing = 0.7 * pixel;
+    float outline_edge = 0.5 - outline_width * pixel;
+
+    vec4 fill = v_x < v_wipe ? v_sung : v_unsung;
+    fill.rgb *= v_shade;
+    vec4 color = vec4(fill.rgb, fill.a * smoothstep(0.5 - smoothing, 0.5 + smoothing, distance));
+    if (outline_width > 0.0) {
+        float outline = smoothstep(outline_edge - smoothing, outline_edge + smoothing, distance);
+        color = over(color, vec4(outline_color.rgb, outline_color.a * fill.a * outline));
+    }
+    if (shadow_color.a > 0.0) {
+        vec2 texel = vec2(dFdx(v_texcoord).x, dFdy(v_texcoord).y);
+        float shadow_distance = texture2D(atlas, v_texcoord - shadow_offset * texel).r;
+        float blur = max(shadow_blur * pixel, smoothing);
+        float shadow = smoothstep(outline_edge - blur, outline_edge + blur, shadow_distance);
+        color = over(color, vec4(shadow_color.rgb, shadow_color.a * fill.a * shadow));
+    }
+    if (glow_radius > 0.0) {
+        float glow = smoothstep(outline_edge - glow_radius * pixel, outline_edge, distance);
+        color = over(color, vec4(glow_color.rgb, glow_color.a * fill.a * glow * glow));
+    }
+    gl_FragColor = color;
+}
+";
+
+/// Position of a glyph in the atlas, in pixels, padded by the spread of the
+/// distance field
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    /// Horizontal distance to the next glyph, in pixels of the rasterized
+    /// glyph
+    advance: u32,
+}
+
+/// Texture holding the distance fields of the glyphs, computed on demand
+struct GlyphAtlas {
+    texture: GLuint,
+    glyphs: HashMap<char, Glyph>,
+    /// The atlas is filled row by row, from left to right
+    cursor: (u32, u32),
+    row_height: u32,
+    full: bool,
+}
+
+/// Computes the distance field of a glyph rasterized in `coverage`, padded
+/// by `SDF_SPREAD` pixels on each side
+fn distance_field(coverage: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+    let spread = SDF_SPREAD as usize;
+    let (field_width, field_height) = (width + 2 * spread, height + 2 * spread);
+    let inside = |x: i32, y: i32| {
+        let (x, y) = (x - SDF_SPREAD, y - SDF_SPREAD);
+        x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height &&
+        coverage[y as usize * width + x as usize] >= 128
+    };
+    let mut field = vec![0u8; field_width * field_height];
+    for y in 0..field_height as i32 {
+        for x in 0..field_width as i32 {
+            let is_inside = inside(x, y);
+            // squared distance to the nearest pixel on the other side of the
+            // edge, looked for within the spread
+            let mut nearest = (SDF_SPREAD * SDF_SPREAD) as f32;
+            for dy in -SDF_SPREAD..SDF_SPREAD + 1 {
+                for dx in -SDF_SPREAD..SDF_SPREAD + 1 {
+                    let squared = (dx * dx + dy * dy) as f32;
+                    if squared < nearest && inside(x + dx, y + dy) != is_inside {
+                        nearest = squared;
+                    }
+                }
+            }
+            let distance = nearest.sqrt() / SDF_SPREAD as f32;
+            let signed = if is_inside {
+                distance
+            } else {
+                -distance
+            };
+            let value = 0.5 + signed / 2.0;
+            field[y as usize * field_width + x as usize] = (value.max(0.0).min(1.0) * 255.0) as u8;
+        }
+    }
+    (field, field_width, field_height)
+}
+
+impl GlyphAtlas {
+    fn new() -> GlyphAtlas {
+        let mut texture = 0;
+        let blank = vec![0u8; (ATLAS_SIZE * ATLAS_SIZE * 4) as usize];
+        unsafe {
+            let previous_texture = bound_texture();
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexImage2D(gl::TEXTURE_2D,
+                           0,
+                           gl::RGBA as GLint,
+                           ATLAS_SIZE as GLsizei,
+                           ATLAS_SIZE as GLsizei,
+                           0,
+                           gl::RGBA,
+                           gl::UNSIGNED_BYTE,
+                           blank.as_ptr() as *const _);
+            gl::BindTexture(gl::TEXTURE_2D, previous_texture);
+        }
+        GlyphAtlas {
+            texture: texture,
+            glyphs: HashMap::new(),
+            cursor: (1, 1),
+            row_height: 0,
+            full: false,
+        }
+    }
+
+    /// Returns the glyph of `c`, computing its distance field into the atlas
+    /// if needed
+    fn glyph(&mut self, c: char, font: &Font) -> Option<Glyph> {
+        if let Some(glyph) = self.glyphs.get(&c) {
+            return Some(*glyph);
+        }
+        if self.full {
+            return None;
+        }
+        let surface = match font.render(&c.to_string()).blended(Color::RGB(255, 255, 255)) {
+            Ok(surface) => surface,
+            Err(_) => return None,
+        };
+        let (width, height) = (surface.width() as usize, surface.height() as usize);
+        let mut coverage = vec![0u8; width * height];
+        surface.with_lock(|pixels: &[u8]| {
+            let pitch = surface.pitch() as usize;
+            for row in 0..height {
+                for column in 0..width {
+                    coverage[row * width + column] = pixels[row * pitch + column * 4 +
+                                                            ARGB_BYTES[0]];
+                }
+            }
+        });
+        let (field, field_width, field_height) = distance_field(&coverage, width, height);
+        let (field_width, field_height) = (field_width as u32, field_height as u32);
+        if self.cursor.0 + field_width + 1 > ATLAS_SIZE {
+            self.cursor = (1, self.cursor.1 + self.row_height + 1);
+            self.row_height = 0;
+        }
+        if self.cursor.1 + field_height + 1 > ATLAS_SIZE {
+            warn!("Glyph atlas full, some characters won't be displayed");
+            self.full = true;
+            return None;
+        }
+        let pixels: Vec<u8> = field.iter()
+                                   .flat_map(|&distance| vec![distance, 0, 0, 255].into_iter())
+                                   .collect();
+        let glyph = Glyph {
+            x: self.cursor.0,
+            y: self.cursor.1,
+            width: field_width,
+            height: field_height,
+            advance: width as u32,
+        };
+        unsafe {
+            let previous_texture = bound_texture();
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexSubImage2D(gl::TEXTURE_2D,
+                              0,
+                              glyph.x as GLint,
+                              glyph.y as GLint,
+                              field_width as GLsizei,
+                              field_height as GLsizei,
+                              gl::RGBA,
+                              gl::UNSIGNED_BYTE,
+                              pixels.as_ptr() as *const _);
+            gl::BindTexture(gl::TEXTURE_2D, previous_texture);
+        }
+        self.cursor.0 += field_width + 1;
+        self.row_height = cmp::max(self.row_height, field_height);
+        self.glyphs.insert(c, glyph);
+        Some(glyph)
+    }
+}
+
+impl Drop for GlyphAtlas {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+unsafe fn bound_texture() -> GLuint {
+    let mut texture = 0;
+    gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut texture);
+    texture as GLuint
+}
+
+/// The part of the OpenGL state the SDL renderer caches, saved before
+/// something else draws in its context. SDL would otherwise keep drawing
+/// with whatever mpv or the text shader left bound.
+pub struct GlState {
+    program: GLint,
+    array_buffer: GLint,
+    active_texture: GLint,
+    texture: GLint,
+    viewport: [GLint; 4],
+    blend: [GLint; 4],
+    blend_enabled: GLboolean,
+}
+
+impl GlState {
+    pub fn save() -> GlState {
+        let mut state = GlState {
+            program: 0,
+            array_buffer: 0,
+            active_texture: 0,
+            texture: 0,
+            viewport: [0; 4],
+            blend: [0; 4],
+            blend_enabled: gl::FALSE,
+        };
+        unsafe {
+            gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut state.program);
+            gl::GetIntegerv(gl::ARRAY_BUFFER_BINDING, &mut state.array_buffer);
+            gl::GetIntegerv(gl::ACTIVE_TEXTURE, &mut state.active_texture);
+            gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut state.texture);
+            gl::GetIntegerv(gl::VIEWPORT, state.viewport.as_mut_ptr());
+            gl::GetIntegerv(gl::BLEND_SRC_RGB, &mut state.blend[0]);
+            gl::GetIntegerv(gl::BLEND_DST_RGB, &mut state.blend[1]);
+            gl::GetIntegerv(gl::BLEND_SRC_ALPHA, &mut state.blend[2]);
+            gl::GetIntegerv(gl::BLEND_DST_ALPHA, &mut state.blend[3]);
+            state.blend_enabled = gl::IsEnabled(gl::BLEND);
+        }
+        state
+    }
+
+    pub fn restore(&self) {
+        unsafe {
+            gl::UseProgram(self.program as GLuint);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.array_buffer as GLuint);
+            gl::ActiveTexture(self.active_texture as GLenum);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture as GLuint);
+            gl::Viewport(self.viewport[0], self.viewport[1], self.viewport[2], self.viewport[3]);
+            gl::BlendFuncSeparate(self.blend[0] as GLenum,
+                                  self.blend[1] as GLenum,
+                                  self.blend[2] as GLenum,
+                                  self.blend[3] as GLenum);
+            if self.blend_enabled == gl::FALSE {
+                gl::Disable(gl::BLEND);
+            } else {
+                gl::Enable(gl::BLEND);
+            }
+        }
+    }
+}
+
+struct Uniforms {
+    screen: GLint,
+    atlas: GLint,
+    outline_color: GLint,
+    outline_width: GLint,
+    glow_color: GLint,
+    glow_radius: GLint,
+    shadow_color: GLint,
+    shadow_offset: GLint,
+    shadow_blur: GLint,
+}
+
+/// Draws text with OpenGL, in the context mpv draws the video in. The
+/// distance fields of the glyphs are computed once into an atlas, then each
+/// frame the quads of the text are batched into a vertex buffer and drawn by
+/// `flush`, at any size.
+pub struct GlText {
+    program: GLuint,
+    buffer: GLuint,
+    attributes: [GLuint; 6],
+    uniforms: Uniforms,
+    font: Font,
+    style: TextStyle,
+    atlas: GlyphAtlas,
+    vertices: Vec<f32>,
+}
+
+fn compile_shader(source: &str, kind: GLenum) -> Result<GLuint, String> {
+    unsafe {
+        let shader = gl::CreateShader(kind);
+        let source = CString::new(source).unwrap();
+        gl::ShaderSource(shader, 1, &source.as_ptr(), ptr::null());
+        gl::CompileShader(shader);
+        let mut status = gl::FALSE as GLint;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+        if status == gl::TRUE as GLint {
+            return Ok(shader);
+        }
+        let mut length = 0;
+        gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length);
+        let mut log = vec![0u8; length as usize];
+        gl::GetShaderInfoLog(shader,
+                             length,
+                             ptr::null_mut(),
+                             log.as_mut_ptr() as *mut GLchar);
+        gl::DeleteShader(shader);
+        Err(String::from_utf8_lossy(&log).into_owned())
+    }
+}
+
+fn color_components(color: Color) -> [f32; 4] {
+    let (r, g, b, a) = color.rgba();
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0]
+}
+
+fn rgb_components((r, g, b): Rgb, alpha: u8) -> [f32; 4] {
+    color_components(Color::RGBA(r, g, b, alpha))
+}
+
+/// Loads the font glyphs are rasterized from, at the size of the distance
+/// fields
+pub fn load_font(font_path: &Path, ttf_context: &sdl2_ttf::Sdl2TtfContext) -> Result<Font, String> {
+    ttf_context.load_font(font_path, SDF_FONT_SIZE)
+               .map_err(|e| format!("cannot load font : {:?}", e))
+}
+
+/// Width of `text` drawn by `GlText` at `font_size`, `font` being loaded by
+/// `load_font`. Each glyph advances by the width it is rasterized at, which
+/// needs no OpenGL context to be measured.
+pub fn text_width(font: &Font, text: &str, font_size: f32) -> f32 {
+    let scale = font_size / SDF_FONT_SIZE as f32;
+    text.chars()
+        .filter_map(|c| font.size_of(&c.to_string()).ok())
+        .fold(0.0, |width, (advance, _)| width + advance as f32 * scale)
+}
+
+impl GlText {
+    pub fn new(font_path: &Path,
+               ttf_context: &sdl2_ttf::Sdl2TtfContext,
+               style: TextStyle)
+               -> Result<GlText, String> {
+        let font = try!(load_font(font_path, ttf_context));
+        let vertex_shader = try!(compile_shader(VERTEX_SHADER, gl::VERTEX_SHADER));
+        let fragment_shader = try!(compile_shader(FRAGMENT_SHADER, gl::FRAGMENT_SHADER));
+        unsafe {
+            let program = gl::CreateProgram();
+            gl::AttachShader(program, vertex_shader);
+            gl::AttachShader(program, fragment_shader);
+            gl::LinkProgram(program);
+            gl::DeleteShader(vertex_shader);
+            gl::DeleteShader(fragment_shader);
+            let mut status = gl::FALSE as GLint;
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+            if status != gl::TRUE as GLint {
+                gl::DeleteProgram(program);
+                return Err("failed to link the text shaders".to_string());
+            }
+            let location = |name: &str| {
+                let name = CString::new(name).unwrap();
+                gl::GetAttribLocation(program, name.as_ptr())
+            };
+            let mut attributes = [0; 6];
+            for (attribute, name) in attributes.iter_mut()
+                                               .zip(["position", "texcoord", "sung", "unsung",
+                                                     "wipe", "shade"]
+                                                        .iter()) {
+                let index = location(name);
+                if index < 0 {
+                    gl::DeleteProgram(program);
+                    return Err(format!("missing shader attribute {}", name));
+                }
+                *attribute = index as GLuint;
+            }
+            let uniform = |name: &str| {
+                let name = CString::new(name).unwrap();
+                gl::GetUniformLocation(program, name.as_ptr())
+            };
+            let mut buffer = 0;
+            gl::GenBuffers(1, &mut buffer);
+            Ok(GlText {
+                program: program,
+                buffer: buffer,
+                attributes: attributes,
+                uniforms: Uniforms {
+                    screen: uniform("screen"),
+                    atlas: uniform("atlas"),
+                    outline_color: uniform("outline_color"),
+                    outline_width: uniform("outline_width"),
+                    glow_color: uniform("glow_color"),
+                    glow_radius: uniform("glow_radius"),
+                    shadow_color: uniform("shadow_color"),
+                    shadow_offset: uniform("shadow_offset"),
+                    shadow_blur: uniform("shadow_blur"),
+                },
+                font: font,
+                style: style,
+                atlas: GlyphAtlas::new(),
+                vertices: Vec::new(),
+            })
+        }
+    }
+
+    /// Changes the colours and effects of the text drawn from now on
+    pub fn set_style(&mut self, style: TextStyle) {
+        self.style = style;
+    }
+
+    /// Height of a line of text drawn at `font_size`, in pixels
+    pub fn line_height(&self, font_size: f32) -> f32 {
+        self.font.height() as f32 * font_size / SDF_FONT_SIZE as f32
+    }
+
+    /// Width of `text` drawn at `font_size`, in pixels
+    pub fn text_width(&self, text: &str, font_size: f32) -> f32 {
+        text_width(&self.font, text, font_size)
+    }
+
+    /// Adds `text` to the batch, its outline starting at (x, y) like text
+    /// drawn by the displayer. Text on the left of `wipe` is drawn in the
+    /// sung color. Returns the width of the text.
+    pub fn push_text(&mut self,
+                     text: &str,
+                     x: f32,
+                     y: f32,
+                     font_size: f32,
+                     sung: Color,
+                     unsung: Color,
+                     wipe: f32)
+                     -> f32 {
+        let scale = font_size / SDF_FONT_SIZE as f32;
+        let (sung, unsung) = (color_components(sung), color_components(unsung));
+        let outline_width = self.style.outline_width as f32;
+        let gradient = self.style.gradient;
+        // the glyphs are padded by the spread of their distance field
+        let padding = SDF_SPREAD as f32 * scale;
+        let mut pen = x + outline_width;
+        for c in text.chars() {
+            let glyph = match self.atlas.glyph(c, &self.font) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            let (left, top) = (pen - padding, y + outline_width - padding);
+            let (right, bottom) = (left + glyph.width as f32 * scale,
+                                   top + glyph.height as f32 * scale);
+            let atlas_size = ATLAS_SIZE as f32;
+            let (u0, v0) = (glyph.x as f32 / atlas_size, glyph.y as f32 / atlas_size);
+            let (u1, v1) = ((glyph.x + glyph.width) as f32 / atlas_size,
+                            (glyph.y + glyph.height) as f32 / atlas_size);
+            let corners = [(left, top, u0, v0, 1.0),
+                           (right, top, u1, v0, 1.0),
+                           (right, bottom, u1, v1, gradient),
+                           (left, top, u0, v0, 1.0),
+                           (right, bottom, u1, v1, gradient),
+                           (left, bottom, u0, v1, gradient)];
+            for &(position_x, position_y, u, v, shade) in &corners {
+                self.vertices.extend_from_slice(&[position_x, position_y, u, v]);
+                self.vertices.extend_from_slice(&sung);
+                self.vertices.extend_from_slice(&unsung);
+                self.vertices.push(wipe);
+                self.vertices.push(shade);
+            }
+            pen += glyph.advance as f32 * scale;
+        }
+        pen - x - outline_width
+    }
+
+    /// Draws the batched text over the current framebuffer, the GL state of
+    /// the SDL renderer is restored afterwards
+    pub fn flush(&mut self, width: u32, height: u32) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let style = self.style;
+        let previous_state = GlState::save();
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::UseProgram(self.program);
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::BindTexture(gl::TEXTURE_2D, self.atlas.texture);
+            gl::Uniform1i(self.uniforms.atlas, 0);
+            gl::Uniform2f(self.uniforms.screen, width as f32, height as f32);
+            let outline_color = rgb_components(style.outline_color, 255);
+            gl::Uniform4fv(self.uniforms.outline_color, 1, outline_color.as_ptr());
+            gl::Uniform1f(self.uniforms.outline_width, style.outline_width as f32);
+            let (glow_color, glow_radius) = match style.glow {
+                Some(glow) => (rgb_components(glow.color, 255), glow.radius as f32),
+                None => ([0.0; 4], 0.0),
+            };
+            gl::Uniform4fv(self.uniforms.glow_color, 1, glow_color.as_ptr());
+            gl::Uniform1f(self.uniforms.glow_radius, glow_radius);
+            let (shadow_color, shadow_offset, shadow_blur) = match style.shadow {
+                Some(shadow) => {
+                    (rgb_components(shadow.color, shadow.alpha),
+                     [shadow.offset.0 as f32, shadow.offset.1 as f32],
+                     shadow.blur as f32)
+                }
+                None => ([0.0; 4], [0.0; 2], 0.0),
+            };
+            gl::Uniform4fv(self.uniforms.shadow_color, 1, shadow_color.as_ptr());
+            gl::Uniform2fv(self.uniforms.shadow_offset, 1, shadow_offset.as_ptr());
+            gl::Uniform1f(self.uniforms.shadow_blur, shadow_blur);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.buffer);
+            gl::BufferData(gl::ARRAY_BUFFER,
+                           (self.vertices.len() * mem::size_of::<f32>()) as GLsizeiptr,
+                           self.vertices.as_ptr() as *const _,
+                           gl::STREAM_DRAW);
+            let stride = (VERTEX_SIZE * mem::size_of::<f32>()) as GLsizei;
+            let mut offset = 0;
+            for (&attribute, &size) in self.attributes.iter().zip([2, 2, 4, 4, 1, 1].iter()) {
+                gl::EnableVertexAttribArray(attribute);
+                gl::VertexAttribPointer(attribute,
+                                        size,
+                                        gl::FLOAT,
+                                        gl::FALSE,
+                                        stride,
+                                        (offset * mem::size_of::<f32>()) as *const _);
+                offset += size as usize;
+            }
+            gl::DrawArrays(gl::TRIANGLES, 0, (self.vertices.len() / VERTEX_SIZE) as GLsizei);
+            for &attribute in &self.attributes {
+                gl::DisableVertexAttribArray(attribute);
+            }
+        }
+        previous_state.restore();
+        self.vertices.clear();
+    }
+}
+
+impl Drop for GlText {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.buffer);
+            gl::DeleteProgram(self.program);
+        }
+    }
+}
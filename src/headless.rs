@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::Renderer;
+use sdl2::surface::{Surface, SurfaceRef};
+
+use mpv::Mpv;
+use mpv_gen::Enum_mpv_event_id;
+
+/// Renderer drawing in memory with the SDL software renderer, it needs
+/// neither a display nor a GPU
+pub fn software_renderer((width, height): (u32, u32)) -> Result<Renderer<'static>, String> {
+    let surface = try!(Surface::new(width, height, PixelFormatEnum::ARGB8888));
+    Renderer::from_surface(surface)
+}
+
+/// mpv decoding files without displaying the video nor playing the audio.
+/// It stays paused, frames are only reached by seeking.
+pub fn null_mpv() -> Result<Mpv, String> {
+    let mpv = try!(Mpv::init().map_err(|e| e.to_string()));
+    let options = [("vo", "null"), ("ao", "null"), ("sid", "no"), ("pause", "yes")];
+    for &(option, value) in &options {
+        try!(mpv.set_option(option, value)
+                .map_err(|e| format!("cannot set option {} : {}", option, e)));
+    }
+    Ok(mpv)
+}
+
+/// Loads a file and waits until mpv knows its tracks and frame rate
+pub fn load_file(mpv: &Mpv, path: &Path) -> Result<(), String> {
+    try!(mpv.command(&["loadfile", &*path.to_string_lossy()]).map_err(|e| e.to_string()));
+    loop {
+        match mpv.wait_event_timeout(-1.0).map(|event| event.event_id) {
+            Some(Enum_mpv_event_id::MPV_EVENT_FILE_LOADED) => return Ok(()),
+            Some(Enum_mpv_event_id::MPV_EVENT_END_FILE) => {
+                return Err("mpv could not play the file".to_string())
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parses a frame size such as `1280x720`
+pub fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let mut parts = size.splitn(2, 'x');
+    match (parts.next().and_then(|w| w.parse().ok()),
+           parts.next().and_then(|h| h.parse().ok())) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => Some((width, height)),
+        _ => None,
+    }
+}
+
+/// Counts the pixels of `frame` with a channel differing by more than
+/// `tolerance` from the one of `golden`. Both must be ARGB8888 surfaces of
+/// the same size.
+pub fn differing_pixels(frame: &SurfaceRef,
+                        golden: &SurfaceRef,
+                        tolerance: u8)
+                        -> Result<usize, String> {
+    if frame.size() != golden.size() {
+        return Err(format!("size is {:?} instead of {:?}", frame.size(), golden.size()));
+    }
+    if golden.pixel_format_enum() != PixelFormatEnum::ARGB8888 {
+        return Err(format!("unexpected pixel format {:?}", golden.pixel_format_enum()));
+    }
+    let (width, height) = (frame.width() as usize, frame.height() as usize);
+    let (frame_pitch, golden_pitch) = (frame.pitch() as usize, golden.pitch() as usize);
+    let count = frame.with_lock(|frame_pixels: &[u8]| {
+        golden.with_lock(|golden_pixels: &[u8]| {
+            let mut count = 0;
+            for row in 0..height {
+                for column in 0..width {
+                    let frame_pixel = &frame_pixels[row * frame_pitch + column * 4..][..4];
+                    let golden_pixel = &golden_pixels[row * golden_pitch + column * 4..][..4];
+                    let differs = frame_pixel.iter().zip(golden_pixel.iter()).any(|(&a, &b)| {
+                        (a as i32 - b as i32).abs() > tolerance as i32
+                    });
+                    if differs {
+                        count += 1;
+                    }
+                }
+            }
+            count
+        })
+    });
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use sdl2::pixels::{Color, PixelFormatEnum};
+    use sdl2::rect::Rect;
+    use sdl2::surface::Surface;
+
+    use displayer::ARGB_BYTES;
+    use super::{differing_pixels, parse_size, software_renderer};
+
+    fn surface(width: u32, height: u32, argb: [u8; 4]) -> Surface<'static> {
+        let mut surface = Surface::new(width, height, PixelFormatEnum::ARGB8888).unwrap();
+        surface.with_lock_mut(|pixels: &mut [u8]| {
+            for pixel in pixels.chunks_mut(4) {
+                for (byte, &index) in argb.iter().zip(ARGB_BYTES.iter()) {
+                    pixel[index] = *byte;
+                }
+            }
+        });
+        surface
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1280x720"), Some((1280, 720)));
+        assert_eq!(parse_size("x"), None);
+        assert_eq!(parse_size("0x0"), None);
+        assert_eq!(parse_size("12"), None);
+        assert_eq!(parse_size("12x"), None);
+        assert_eq!(parse_size("-1x10"), None);
+    }
+
+    #[test]
+    fn pixel_differences() {
+        let golden = surface(4, 3, [255, 100, 100, 100]);
+        let close = surface(4, 3, [255, 104, 96, 100]);
+        assert_eq!(differing_pixels(&golden, &golden, 0), Ok(0));
+        assert_eq!(differing_pixels(&close, &golden, 0), Ok(12));
+        assert_eq!(differing_pixels(&close, &golden, 4), Ok(0));
+        assert_eq!(differing_pixels(&close, &golden, 3), Ok(12));
+        assert!(differing_pixels(&surface(3, 4, [255, 0, 0, 0]), &golden, 255).is_err());
+    }
+
+    /// The software renderer draws the frames compared by the frames command
+    #[test]
+    fn golden_frame() {
+        let mut renderer = software_renderer((8, 4)).unwrap();
+        renderer.set_draw_color(Color::RGB(0, 0, 0));
+        renderer.clear();
+        renderer.set_draw_color(Color::RGB(230, 159, 0));
+        renderer.fill_rect(Rect::new(2, 1, 4, 2));
+        renderer.present();
+        let golden_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/headless-8x4.bmp");
+        let golden = Surface::load_bmp(&golden_path).unwrap();
+        assert_eq!(differing_pixels(renderer.surface().unwrap(), &golden, 0), Ok(0));
+    }
+}
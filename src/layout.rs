@@ -0,0 +1,76 @@
+use mpv::Mpv;
+
+/// A rectangle of the window, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Area {
+    pub fn window((width, height): (u32, u32)) -> Area {
+        Area {
+            x: 0,
+            y: 0,
+            width: width,
+            height: height,
+        }
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+}
+
+/// Largest area with the aspect ratio of the video, centered in the window
+pub fn fit((video_width, video_height): (u32, u32), window: (u32, u32)) -> Area {
+    let (window_width, window_height) = window;
+    if video_width == 0 || video_height == 0 {
+        return Area::window(window);
+    }
+    let video_aspect = video_width as f64 / video_height as f64;
+    let window_aspect = window_width as f64 / window_height as f64;
+    let (width, height) = if video_aspect > window_aspect {
+        (window_width, (window_width as f64 / video_aspect).round() as u32)
+    } else {
+        ((window_height as f64 * video_aspect).round() as u32, window_height)
+    };
+    Area {
+        x: (window_width as i32 - width as i32) / 2,
+        y: (window_height as i32 - height as i32) / 2,
+        width: width,
+        height: height,
+    }
+}
+
+/// Returns where mpv draws the video in the window, from the margins of
+/// `osd-dimensions`, or by fitting the display size of `video-params`. Files
+/// without video get None.
+pub fn video_area(mpv: &Mpv, window: (u32, u32)) -> Option<Area> {
+    let margin = |name: &str| mpv.get_property::<i64>(&format!("osd-dimensions/{}", name));
+    let osd_width = mpv.get_property::<i64>("osd-dimensions/w").unwrap_or(0);
+    match (margin("ml"), margin("mr"), margin("mt"), margin("mb")) {
+        (Ok(left), Ok(right), Ok(top), Ok(bottom)) if osd_width as u32 == window.0 => {
+            let width = window.0 as i64 - left - right;
+            let height = window.1 as i64 - top - bottom;
+            if width > 0 && height > 0 {
+                return Some(Area {
+                    x: left as i32,
+                    y: top as i32,
+                    width: width as u32,
+                    height: height as u32,
+                });
+            }
+        }
+        _ => {}
+    }
+    match (mpv.get_property::<i64>("video-params/dw"),
+           mpv.get_property::<i64>("video-params/dh")) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => {
+            Some(fit((width as u32, height as u32), window))
+        }
+        _ => None,
+    }
+}
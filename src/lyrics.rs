@@ -0,0 +1,616 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Frame rate frm files are assumed to use when the video's one is unknown
+pub const DEFAULT_FPS: f64 = 25.0;
+
+/// A single timed syllable, `begin` and `end` are in milliseconds so they
+/// do not depend on the frame rate of the video
+#[derive(Debug, Clone)]
+pub struct Syllable {
+    pub text: String,
+    pub begin: u32,
+    pub end: u32,
+    /// Indexes in `Song::singers`, empty when the syllable isn't assigned
+    /// to any singer
+    pub singers: Vec<usize>,
+    pub source: Source,
+}
+
+/// Where a syllable has been read from, lines and columns start at 1
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Source {
+    pub lyr_line: usize,
+    pub lyr_column: usize,
+    pub frm_line: usize,
+}
+
+/// A line of a frm file
+#[derive(Debug, Clone, Copy)]
+pub struct FrameRange {
+    pub line: usize,
+    pub begin: u32,
+    pub end: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub syllables: Vec<Syllable>,
+}
+
+pub type Rgb = (u8, u8, u8);
+
+/// Part of the screen a line is displayed in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(Debug, Clone)]
+pub struct Singer {
+    pub id: String,
+    pub name: String,
+    pub region: Region,
+    pub sung_color: Rgb,
+    pub unsung_color: Rgb,
+}
+
+pub const DEFAULT_SUNG_COLOR: Rgb = (255, 100, 60);
+pub const DEFAULT_UNSUNG_COLOR: Rgb = (180, 180, 180);
+
+impl Singer {
+    fn new(id: &str) -> Singer {
+        Singer {
+            id: id.to_string(),
+            name: id.to_string(),
+            region: Region::Top,
+            sung_color: DEFAULT_SUNG_COLOR,
+            unsung_color: DEFAULT_UNSUNG_COLOR,
+        }
+    }
+}
+
+/// Information about a song, displayed on its title card
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    /// Anime, game or film the song comes from
+    pub source: Option<String>,
+    /// People who timed the lyrics
+    pub timers: Vec<String>,
+    /// Theme the song is displayed with, from `%theme <name>`
+    pub theme: Option<String>,
+    /// Colours of the theme overridden by `%color <key> <RRGGBB>`
+    pub colors: Vec<(String, Rgb)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Song {
+    pub lines: Vec<Line>,
+    pub singers: Vec<Singer>,
+    pub metadata: Metadata,
+}
+
+#[derive(Debug)]
+pub enum LyricsError {
+    Io(io::Error),
+    InvalidFrame {
+        line: usize,
+        content: String,
+    },
+    SyllableCountMismatch {
+        lyr: usize,
+        frm: usize,
+    },
+    /// A frame rate which is not a positive number
+    InvalidFps(f64),
+}
+
+impl From<io::Error> for LyricsError {
+    fn from(error: io::Error) -> LyricsError {
+        LyricsError::Io(error)
+    }
+}
+
+impl Error for LyricsError {
+    fn description(&self) -> &str {
+        match *self {
+            LyricsError::Io(ref error) => error.description(),
+            LyricsError::InvalidFrame { .. } => "invalid line in frm file",
+            LyricsError::SyllableCountMismatch { .. } => "syllable count differs between lyr and frm",
+            LyricsError::InvalidFps(_) => "invalid frame rate",
+        }
+    }
+}
+
+impl fmt::Display for LyricsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LyricsError::Io(ref error) => write!(f, "{}", error),
+            LyricsError::InvalidFrame { line, ref content } => {
+                write!(f, "invalid frm line {} : '{}'", line, content)
+            }
+            LyricsError::SyllableCountMismatch { lyr, frm } => {
+                write!(f, "lyr has {} syllables but frm has {}", lyr, frm)
+            }
+            LyricsError::InvalidFps(fps) => write!(f, "invalid frame rate {}", fps),
+        }
+    }
+}
+
+impl Line {
+    pub fn begin(&self) -> u32 {
+        self.syllables.first().map(|s| s.begin).unwrap_or(0)
+    }
+
+    pub fn end(&self) -> u32 {
+        self.syllables.last().map(|s| s.end).unwrap_or(0)
+    }
+
+    pub fn text(&self) -> String {
+        self.syllables.iter().map(|s| &*s.text).collect()
+    }
+
+    /// Number of syllables whose singing has begun at `time`, and how much of
+    /// the last of them is sung, between 0 and 1
+    pub fn progress(&self, time: u32) -> (usize, f32) {
+        let sung = self.syllables.iter().filter(|s| s.begin <= time).count();
+        let progress = match sung.checked_sub(1).map(|index| &self.syllables[index]) {
+            Some(syllable) if syllable.end > syllable.begin => {
+                (time - syllable.begin) as f32 / (syllable.end - syllable.begin) as f32
+            }
+            _ => 1.0,
+        };
+        (sung, progress)
+    }
+
+    /// Every singer singing at least one syllable of the line
+    pub fn singers(&self) -> Vec<usize> {
+        let mut singers: Vec<usize> = Vec::new();
+        for &singer in self.syllables.iter().flat_map(|s| s.singers.iter()) {
+            if !singers.contains(&singer) {
+                singers.push(singer);
+            }
+        }
+        singers
+    }
+}
+
+impl Song {
+    /// Loads a song whose syllables are timed by the given frm file, the frame
+    /// numbers are converted to milliseconds using `fps`
+    pub fn load(lyr_path: &Path, frm_path: &Path, fps: f64) -> Result<Song, LyricsError> {
+        let lyr = try!(read_file(lyr_path));
+        let frm = try!(read_file(frm_path));
+        Song::parse(&lyr, &frm, fps)
+    }
+
+    /// Loads a song without any timing, every timestamp is set to 0
+    pub fn load_untimed(lyr_path: &Path) -> Result<Song, LyricsError> {
+        let lyr = try!(read_file(lyr_path));
+        Ok(parse_lyr(&lyr))
+    }
+
+    pub fn parse(lyr: &str, frm: &str, fps: f64) -> Result<Song, LyricsError> {
+        let mut song = parse_lyr(lyr);
+        let frames = try!(parse_frm(frm));
+        let syllable_count = song.lines.iter().fold(0, |count, l| count + l.syllables.len());
+        if syllable_count != frames.len() {
+            return Err(LyricsError::SyllableCountMismatch {
+                lyr: syllable_count,
+                frm: frames.len(),
+            });
+        }
+        let mut frames = frames.into_iter();
+        for syllable in song.lines.iter_mut().flat_map(|l| l.syllables.iter_mut()) {
+            let frame = frames.next().unwrap();
+            syllable.begin = frame_to_ms(frame.begin, fps);
+            syllable.end = frame_to_ms(frame.end, fps);
+            syllable.source.frm_line = frame.line;
+        }
+        Ok(song)
+    }
+
+    pub fn write_frm(&self, frm_path: &Path, fps: f64) -> io::Result<()> {
+        let mut file = try!(File::create(frm_path));
+        for syllable in self.syllables() {
+            try!(writeln!(file,
+                          "{} {}",
+                          ms_to_frame(syllable.begin, fps),
+                          ms_to_frame(syllable.end, fps)));
+        }
+        Ok(())
+    }
+
+    pub fn syllables(&self) -> Vec<&Syllable> {
+        self.lines.iter().flat_map(|l| l.syllables.iter()).collect()
+    }
+
+    /// Returns the indexes of the lines displayed at `time` (in ms), one per
+    /// region of the screen : in a duet both singers can have a line
+    /// displayed at the same time. A line is displayed until its last
+    /// syllable, or `linger` ms more while it disappears. It leaves its region
+    /// to the next line of the region once that one has begun, or is
+    /// appearing since it begins in less than `lead` ms.
+    pub fn visible_line_indexes(&self, time: u32, linger: u32, lead: u32) -> Vec<usize> {
+        // region and line displayed in it
+        let mut regions: Vec<(Region, usize)> = Vec::new();
+        for (index, line) in self.lines
+                                 .iter()
+                                 .enumerate()
+                                 .filter(|&(_, line)| line.end() + linger >= time) {
+            let region = self.line_region(line);
+            match regions.iter().position(|&(r, _)| r == region) {
+                Some(position) => {
+                    let lingering = time > self.lines[regions[position].1].end();
+                    if lingering && line.begin() <= time + lead {
+                        regions[position].1 = index;
+                    }
+                }
+                None => regions.push((region, index)),
+            }
+        }
+        let mut indexes: Vec<usize> = regions.into_iter().map(|(_, index)| index).collect();
+        indexes.sort();
+        indexes
+    }
+
+    /// When the next line follows an instrumental gap of at least `gap` ms and
+    /// begins in less than `duration` ms, returns it with the remaining part
+    /// of the countdown, going from 1 down to 0
+    pub fn countdown(&self, time: u32, gap: u32, duration: u32) -> Option<(&Line, f32)> {
+        let index = match self.lines.iter().position(|line| line.begin() > time) {
+            Some(index) => index,
+            None => return None,
+        };
+        let next = &self.lines[index];
+        let previous_end = self.lines[..index].iter().map(|line| line.end()).max().unwrap_or(0);
+        let remaining = next.begin() - time;
+        if next.begin().saturating_sub(previous_end) < gap || remaining > duration {
+            None
+        } else {
+            Some((next, remaining as f32 / duration as f32))
+        }
+    }
+
+    /// Lines sung by a single singer go to their region, lines sung by
+    /// singers from different regions go to the middle of the screen
+    pub fn line_region(&self, line: &Line) -> Region {
+        let mut regions = line.singers().into_iter().map(|singer| self.singers[singer].region);
+        match regions.next() {
+            None => Region::Top,
+            Some(first) => {
+                if regions.all(|region| region == first) {
+                    first
+                } else {
+                    Region::Middle
+                }
+            }
+        }
+    }
+
+    /// Returns the sung and unsung colors of a syllable, syllables sung by
+    /// several singers get a mix of their colors
+    pub fn syllable_colors(&self, syllable: &Syllable) -> (Rgb, Rgb) {
+        if syllable.singers.is_empty() {
+            return (DEFAULT_SUNG_COLOR, DEFAULT_UNSUNG_COLOR);
+        }
+        let singers: Vec<&Singer> = syllable.singers.iter().map(|&s| &self.singers[s]).collect();
+        (mix_colors(singers.iter().map(|s| s.sung_color).collect()),
+         mix_colors(singers.iter().map(|s| s.unsung_color).collect()))
+    }
+}
+
+fn mix_colors(colors: Vec<Rgb>) -> Rgb {
+    let count = colors.len() as u32;
+    let (r, g, b) = colors.iter().fold((0, 0, 0), |(r, g, b), &(cr, cg, cb)| {
+        (r + cr as u32, g + cg as u32, b + cb as u32)
+    });
+    ((r / count) as u8, (g / count) as u8, (b / count) as u8)
+}
+
+/// Parses a color written as RRGGBB, with an optional leading '#'
+pub fn parse_color(text: &str) -> Option<Rgb> {
+    let text = text.trim_left_matches('#');
+    // checked digit by digit, from_str_radix would take a sign
+    if text.len() != 6 || !text.chars().all(|c| c.is_digit(16)) {
+        return None;
+    }
+    let color = u32::from_str_radix(text, 16).unwrap();
+    Some(((color >> 16) as u8, (color >> 8) as u8, color as u8))
+}
+
+/// Whether frames can be converted from and to milliseconds at `fps`
+pub fn is_valid_fps(fps: f64) -> bool {
+    fps.is_finite() && fps > 0.0
+}
+
+pub fn frame_to_ms(frame: u32, fps: f64) -> u32 {
+    (frame as f64 * 1000.0 / fps).round() as u32
+}
+
+pub fn ms_to_frame(time: u32, fps: f64) -> u32 {
+    (time as f64 * fps / 1000.0).round() as u32
+}
+
+/// Rescales the frame numbers of a frm file made for a `from_fps` video so
+/// they match the same timestamps in a `to_fps` video
+pub fn retime_frm(frm: &str, from_fps: f64, to_fps: f64) -> Result<String, LyricsError> {
+    for &fps in &[from_fps, to_fps] {
+        if !is_valid_fps(fps) {
+            return Err(LyricsError::InvalidFps(fps));
+        }
+    }
+    let frames = try!(parse_frm(frm));
+    // scaled directly, going through milliseconds would round twice
+    let retime = |frame: u32| (frame as f64 * to_fps / from_fps).round() as u32;
+    let mut result = String::new();
+    for frame in frames {
+        result.push_str(&format!("{} {}\n", retime(frame.begin), retime(frame.end)));
+    }
+    Ok(result)
+}
+
+pub fn retime_frm_file(frm_path: &Path,
+                       output_path: &Path,
+                       from_fps: f64,
+                       to_fps: f64)
+                       -> Result<(), LyricsError> {
+    let frm = try!(read_file(frm_path));
+    let retimed = try!(retime_frm(&frm, from_fps, to_fps));
+    let mut file = try!(File::create(output_path));
+    try!(file.write_all(retimed.as_bytes()));
+    Ok(())
+}
+
+/// Returns the lyr and frm paths living next to a video file
+pub fn sidecar_paths(video_path: &Path) -> (PathBuf, PathBuf) {
+    (video_path.with_extension("lyr"), video_path.with_extension("frm"))
+}
+
+pub fn read_file(path: &Path) -> io::Result<String> {
+    let mut content = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut content));
+    Ok(content)
+}
+
+/// Every line of a lyr file is a lyrics line, each syllable begins with a '&'.
+/// Lines beginning with '%' are directives :
+///
+/// * `%singer <id> <top|middle|bottom> <sung RRGGBB> <unsung RRGGBB> [name]`
+///   declares a singer, with the region and colors of its lines
+/// * `%part [id...]` assigns the following lines to the given singers, or to
+///   nobody in particular when no id is given
+/// * `%title`, `%artist` and `%source` followed by some text, and `%timer`
+///   followed by a name, which may be repeated, describe the song
+/// * `%theme <name>` displays the song with a theme of the configuration
+/// * `%color <sung|unsung|active|outline|osd> <RRGGBB>` overrides a colour of
+///   the theme for this song
+///
+/// A single syllable can be assigned to other singers than its line by
+/// starting it with their ids between braces, for instance `&{A,B}la`.
+/// Singers which are not declared get the default region and colors.
+pub fn parse_lyr(lyr: &str) -> Song {
+    let mut song = Song {
+        lines: Vec::new(),
+        singers: Vec::new(),
+        metadata: Metadata::default(),
+    };
+    let mut part: Vec<usize> = Vec::new();
+    for (index, line) in lyr.lines().enumerate() {
+        let line = line.trim_right_matches('\r');
+        if line.starts_with('%') {
+            let mut words = line[1..].split_whitespace();
+            let directive = words.next();
+            let text = words.clone().collect::<Vec<&str>>().join(" ");
+            // a directive without text is ignored, empty text can't be drawn
+            let value = if text.is_empty() {
+                None
+            } else {
+                Some(text)
+            };
+            match directive {
+                Some("singer") => parse_singer(&mut song.singers, words),
+                Some("part") => part = words.map(|id| singer_index(&mut song.singers, id)).collect(),
+                Some("title") => song.metadata.title = value,
+                Some("artist") => song.metadata.artist = value,
+                Some("source") => song.metadata.source = value,
+                Some("timer") => song.metadata.timers.extend(value),
+                Some("theme") => song.metadata.theme = value,
+                Some("color") => {
+                    match (words.next(), words.next().and_then(parse_color)) {
+                        (Some(key), Some(color)) => {
+                            song.metadata.colors.push((key.to_string(), color))
+                        }
+                        _ => warn!("Invalid color directive on line {}", index + 1),
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let mut syllables = Vec::new();
+        let mut column = 1;
+        for text in line.split('&') {
+            let mut syllable_text = text;
+            let mut singers = part.clone();
+            if text.starts_with('{') {
+                if let Some(end) = text.find('}') {
+                    singers = text[1..end]
+                                  .split(|c| c == ',' || c == '+')
+                                  .map(|id| singer_index(&mut song.singers, id.trim()))
+                                  .collect();
+                    syllable_text = &text[end + 1..];
+                }
+            }
+            if !syllable_text.is_empty() {
+                syllables.push(Syllable {
+                    text: syllable_text.to_string(),
+                    begin: 0,
+                    end: 0,
+                    singers: singers,
+                    source: Source {
+                        lyr_line: index + 1,
+                        lyr_column: column + text.chars().count() -
+                                    syllable_text.chars().count(),
+                        frm_line: 0,
+                    },
+                });
+            }
+            // the '&' separator takes one column
+            column += text.chars().count() + 1;
+        }
+        if !syllables.is_empty() {
+            song.lines.push(Line { syllables: syllables });
+        }
+    }
+    song
+}
+
+/// Returns the index of the singer with the given id, declaring it if needed
+pub fn singer_index(singers: &mut Vec<Singer>, id: &str) -> usize {
+    match singers.iter().position(|singer| singer.id == id) {
+        Some(index) => index,
+        None => {
+            singers.push(Singer::new(id));
+            singers.len() - 1
+        }
+    }
+}
+
+fn parse_singer<'a, I: Iterator<Item = &'a str>>(singers: &mut Vec<Singer>, mut words: I) {
+    let id = match words.next() {
+        Some(id) => id,
+        None => return,
+    };
+    let index = singer_index(singers, id);
+    let singer = &mut singers[index];
+    match words.next() {
+        Some("top") => singer.region = Region::Top,
+        Some("middle") => singer.region = Region::Middle,
+        Some("bottom") => singer.region = Region::Bottom,
+        _ => {}
+    }
+    if let Some(color) = words.next().and_then(parse_color) {
+        singer.sung_color = color;
+    }
+    if let Some(color) = words.next().and_then(parse_color) {
+        singer.unsung_color = color;
+    }
+    let name: Vec<&str> = words.collect();
+    if !name.is_empty() {
+        singer.name = name.join(" ");
+    }
+}
+
+/// Every non-empty line of a frm file holds the begin and end frame of a syllable
+pub fn parse_frm(frm: &str) -> Result<Vec<FrameRange>, LyricsError> {
+    let mut frames = Vec::new();
+    for (index, line) in frm.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut numbers = line.split_whitespace().map(|n| n.parse::<u32>());
+        match (numbers.next(), numbers.next(), numbers.next()) {
+            (Some(Ok(begin)), Some(Ok(end)), None) => {
+                frames.push(FrameRange {
+                    line: index + 1,
+                    begin: begin,
+                    end: end,
+                })
+            }
+            _ => {
+                return Err(LyricsError::InvalidFrame {
+                    line: index + 1,
+                    content: line.to_string(),
+                })
+            }
+        }
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_color, parse_lyr, retime_frm, Line, LyricsError, Song, Source, Syllable};
+
+    fn song(timings: &[(u32, u32)]) -> Song {
+        let mut song = parse_lyr("");
+        for &(begin, end) in timings {
+            song.lines.push(Line {
+                syllables: vec![Syllable {
+                                    text: "la".to_string(),
+                                    begin: begin,
+                                    end: end,
+                                    singers: Vec::new(),
+                                    source: Source::default(),
+                                }],
+            });
+        }
+        song
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("ff8000"), Some((255, 128, 0)));
+        assert_eq!(parse_color("#0A0b0C"), Some((10, 11, 12)));
+        assert_eq!(parse_color("+fffff"), None);
+        assert_eq!(parse_color("fffff"), None);
+        assert_eq!(parse_color("gggggg"), None);
+        assert_eq!(parse_color("a\u{20ac}bc"), None);
+        assert_eq!(parse_color("\u{e9}\u{e9}\u{e9}"), None);
+    }
+
+    #[test]
+    fn empty_metadata() {
+        let song = parse_lyr("%title\n%artist  \n%timer\n%source Anime\n");
+        assert_eq!(song.metadata.title, None);
+        assert_eq!(song.metadata.artist, None);
+        assert!(song.metadata.timers.is_empty());
+        assert_eq!(song.metadata.source, Some("Anime".to_string()));
+    }
+
+    #[test]
+    fn lingering_lines() {
+        let song = song(&[(0, 1000), (1200, 2000), (5000, 6000)]);
+        assert_eq!(song.visible_line_indexes(500, 500, 0), vec![0]);
+        // the first line lingers until the second one begins
+        assert_eq!(song.visible_line_indexes(1100, 500, 0), vec![0]);
+        assert_eq!(song.visible_line_indexes(1200, 500, 0), vec![1]);
+        // or until it appears
+        assert_eq!(song.visible_line_indexes(1100, 500, 300), vec![1]);
+        // and lingers fully before a gap
+        assert_eq!(song.visible_line_indexes(2400, 500, 300), vec![1]);
+        assert_eq!(song.visible_line_indexes(2600, 500, 300), vec![2]);
+        assert_eq!(song.visible_line_indexes(1100, 0, 0), vec![1]);
+    }
+
+    #[test]
+    fn retime() {
+        assert_eq!(retime_frm("25 50\n100 125\n", 25.0, 50.0).unwrap(),
+                   "50 100\n200 250\n");
+        assert_eq!(retime_frm("1001 2002\n", 30.0, 30000.0 / 1001.0).unwrap(), "1000 2000\n");
+        assert_eq!(retime_frm("3 5\n", 24.0, 25.0).unwrap(), "3 5\n");
+        assert_eq!(retime_frm("", 25.0, 30.0).unwrap(), "");
+    }
+
+    #[test]
+    fn retime_invalid_fps() {
+        for &(from, to) in &[(0.0, 25.0), (25.0, -1.0), (::std::f64::NAN, 25.0),
+                             (25.0, ::std::f64::INFINITY)] {
+            match retime_frm("1 2\n", from, to) {
+                Err(LyricsError::InvalidFps(_)) => {}
+                result => panic!("{} to {} fps gave {:?}", from, to, result),
+            }
+        }
+        assert!(retime_frm("1 x\n", 25.0, 30.0).is_err());
+    }
+}
@@ -16,7 +16,7 @@ extern crate env_logger;
 
 use gl::types::* ;
 
-use std::time;
+use std::cmp;
 
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
@@ -26,31 +26,130 @@ use sdl2_sys::video::SDL_WindowFlags;
 use std::ffi::CStr;
 use std::os::raw as libc;
 use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+mod animation;
+mod background;
+mod cards;
+mod check;
+mod config;
+mod diagnostics;
 mod displayer ;
+mod editor;
+mod gltext;
+mod headless;
+mod layout;
+mod lyrics;
+mod monitor;
+mod settings;
+mod style;
 
 
 mod mpv;
 mod mpv_gen;
+mod osd;
+mod queue;
+mod render;
+mod romaji;
+mod screenshot;
+mod subtitles;
+mod theme;
+mod tracks;
+mod variants;
+mod wakeup;
+mod watcher;
+
+use mpv_gen::Enum_mpv_event_id;
 
 const USAGE: &'static str = "
 toyunda-player.
 
 Usage:
-  toyunda-player [options] <file>
+  toyunda-player [options] [<file>...]
+  toyunda-player retime <frm> <from-fps> <to-fps> [--output=<frm>]
+  toyunda-player check [--config=<file>] <lyr>...
+  toyunda-player frames [--config=<file>] [--size=<size>] [--output=<dir>] [--compare=<dir>] <video> <time>...
+  toyunda-player render [--config=<file>] [--size=<size>] <video> <output>
   toyunda-player -h | --help
   toyunda-player --version
 
 Options:
-  -h --help     Show this screen.
-  --version     Show version.
-  --invert      Invert the screen.
+  -h --help        Show this screen.
+  --version        Show version.
+  --invert         Invert the screen.
+  --editor         Timing editor mode : hold Return while a syllable is sung,
+                   Backspace goes back one syllable, S saves the frm file.
+  --config=<file>  JSON configuration file.
+  --queue=<file>   Songs to play after the given files, one per line,
+                   optionally followed by a tab and the name of the singer.
+  --diagnostics=<csv>  Writes the timings of every frame to a CSV file.
+  --output=<frm>   Where to write the retimed frm file, defaults to
+                   overwriting the original one. For frames, the directory
+                   of the images, defaults to the current one.
+  --size=<size>    Size of the frames and rendered videos [default: 960x540].
+  --compare=<dir>  Compares the frames to the images of a previous run in
+                   this directory instead of writing them.
+
+Keypad + and - delay or advance the lyrics, the offset is saved per song.
+R switches between original lyrics, romaji, and both.
+V cycles the sung lyrics variant, T the one displayed under it.
+I shows the title and the playback position.
+C cycles the colour themes.
+F3 shows frame timings, dropped frames and A/V sync.
+F12 saves a PNG screenshot of the window, lyrics and OSD included.
+
+The check command reports problems in lyr and frm files, it exits with a
+non-zero status if it finds any error.
+
+The frames command draws the lyrics of a video at the given times, in
+milliseconds, into BMP images named <video>-<time>.bmp. It needs neither a
+display nor a GPU : the lyrics are drawn by the SDL software renderer over a
+black background, and mpv only decodes the file.
+
+The render command writes a copy of a video with the lyrics drawn over it,
+for instance to <output>.mp4, with ffmpeg. Frames are rendered one after the
+other as fast as possible, without display nor GPU either.
 ";
 
+/// Lyrics offset change in milliseconds for each key press
+const OFFSET_STEP: i32 = 50;
+
+/// Longest the main loop sleeps, in milliseconds, the lyrics watcher polls
+/// and the slideshow changes images even when nothing else happens
+const IDLE_TIMEOUT: u32 = 250;
+
+/// Lines displayed after the current one on the stage monitor
+const STAGE_UPCOMING_LINES: usize = 3;
+
+/// Songs of the queue listed on the stage monitor after the current one
+const STAGE_QUEUE_LENGTH: usize = 2;
+
+/// Difference allowed on each channel between a frame and its golden image
+const GOLDEN_TOLERANCE: u8 = 8;
+
 #[derive(Debug, RustcDecodable)]
 struct CmdArgs {
     flag_invert: bool,
-    arg_file: String,
+    flag_editor: bool,
+    flag_output: Option<String>,
+    flag_config: Option<String>,
+    flag_queue: Option<String>,
+    flag_diagnostics: Option<String>,
+    flag_size: String,
+    flag_compare: Option<String>,
+    cmd_retime: bool,
+    cmd_check: bool,
+    cmd_frames: bool,
+    cmd_render: bool,
+    arg_file: Vec<String>,
+    arg_frm: String,
+    arg_from_fps: f64,
+    arg_to_fps: f64,
+    arg_lyr: Vec<String>,
+    arg_video: String,
+    arg_time: Vec<u32>,
+    arg_output: String,
 }
 
 unsafe extern "C" fn do_pote(arg: *mut libc::c_void,
@@ -65,6 +164,344 @@ fn get_mpv_gl(mpv: &mpv::Mpv, video_subsystem: &mut sdl2::VideoSubsystem) -> mpv
     mpv.get_opengl_context(Some(do_pote), ptr).unwrap()
 }
 
+/// Frame rate of the video, some streams report 0 or NaN
+fn video_fps(mpv: &mpv::Mpv) -> f64 {
+    ["container-fps", "estimated-vf-fps"]
+        .iter()
+        .filter_map(|property| mpv.get_property::<f64>(property).ok())
+        .find(|&fps| lyrics::is_valid_fps(fps))
+        .unwrap_or(lyrics::DEFAULT_FPS)
+}
+
+fn display_lyrics(displayer: &mut displayer::Displayer,
+                  song: &lyrics::Song,
+                  time: u32,
+                  row: i32,
+                  transitions: &animation::LineTransitions) {
+    for index in song.visible_line_indexes(time, transitions.linger(), transitions.lead()) {
+        let line = &song.lines[index];
+        if let Some(appearance) = transitions.appearance(line, time) {
+            let (sung, progress) = line.progress(time);
+            displayer.display_line(song, line, sung, progress, row, &appearance);
+        }
+    }
+}
+
+/// Draws what a software renderer shows of a song : its lyrics and countdown,
+/// over the background dimmed by the theme
+fn display_overlay(displayer: &mut displayer::Displayer,
+                   song: &lyrics::Song,
+                   time: u32,
+                   config: &config::Config) {
+    displayer.dim_background();
+    display_lyrics(displayer, song, time, 0, &config.transitions);
+    if let Some((line, remaining)) = song.countdown(time,
+                                                    config.countdown_gap,
+                                                    config.countdown_duration) {
+        displayer.display_countdown(song, line, remaining);
+    }
+}
+
+/// How long the main loop may wait for events, in milliseconds : a frame
+/// while lyrics are animated, until the first `deadline` otherwise, when a
+/// card or the OSD changes
+fn redraw_timeout(animated: bool, fps: f64, deadlines: &[Option<u32>]) -> u32 {
+    if animated {
+        return (1000.0 / fps) as u32;
+    }
+    deadlines.iter().filter_map(|&deadline| deadline).fold(IDLE_TIMEOUT, cmp::min)
+}
+
+/// Writes the timings of the editor, a failure is reported without losing
+/// them so that saving can be retried
+fn save_editor(editor: &mut editor::Editor, fps: f64, displayer: &mut displayer::Displayer) {
+    match editor.save(fps) {
+        Ok(()) => displayer.notify("Timings saved".to_string()),
+        Err(e) => {
+            error!("Failed to write frm file : {}", e);
+            displayer.notify(format!("Failed to save the timings : {}", e));
+        }
+    }
+}
+
+/// Displays the lyrics and messages of both windows with the current theme,
+/// returns its name
+fn apply_theme(themes: &theme::Themes,
+               metadata: &lyrics::Metadata,
+               displayer: &mut displayer::Displayer,
+               stage_monitor: &mut Option<displayer::Displayer>)
+               -> String {
+    let theme = themes.current(metadata);
+    let name = theme.name.clone();
+    if let Some(ref mut stage_monitor) = *stage_monitor {
+        stage_monitor.set_theme(theme.clone());
+    }
+    displayer.set_theme(theme);
+    name
+}
+
+/// Speed set by a keypad digit, 0 being the normal speed
+fn keypad_speed(keycode: Keycode) -> Option<f64> {
+    match keycode {
+        Keycode::Kp0 => Some(1.0),
+        Keycode::Kp1 => Some(0.1),
+        Keycode::Kp2 => Some(0.2),
+        Keycode::Kp3 => Some(0.3),
+        Keycode::Kp4 => Some(0.4),
+        Keycode::Kp5 => Some(0.5),
+        Keycode::Kp6 => Some(0.6),
+        Keycode::Kp7 => Some(0.7),
+        Keycode::Kp8 => Some(0.8),
+        Keycode::Kp9 => Some(0.9),
+        _ => None,
+    }
+}
+
+fn elapsed_ms(since: Instant) -> u32 {
+    let elapsed = since.elapsed();
+    elapsed.as_secs() as u32 * 1000 + elapsed.subsec_nanos() / 1_000_000
+}
+
+/// Current position and duration of the media, as in `1:23 / 3:45`
+fn playback_position(mpv: &mpv::Mpv, time: u32) -> String {
+    match mpv.get_property::<f64>("duration") {
+        Ok(duration) => {
+            format!("{} / {}",
+                    osd::format_time(time),
+                    osd::format_time((duration * 1000.0) as u32))
+        }
+        Err(_) => osd::format_time(time),
+    }
+}
+
+/// Loads the lyrics variants next to the video, or the lyrics embedded in
+/// its subtitle tracks when there is no lyr file
+fn load_lyrics(mpv: &mpv::Mpv,
+               video_path: &Path,
+               fps: f64)
+               -> Result<variants::Variants, String> {
+    let (lyr_path, _) = lyrics::sidecar_paths(video_path);
+    if !lyr_path.exists() {
+        info!("No lyr file, looking for lyrics in subtitle tracks");
+        return subtitles::load_embedded(mpv, video_path)
+                   .map(variants::Variants::single)
+                   .ok_or_else(|| "no lyr file nor subtitle track found".to_string());
+    }
+    variants::Variants::load(video_path, fps).map_err(|e| e.to_string())
+}
+
+fn retime(args: &CmdArgs) {
+    let frm_path = Path::new(&args.arg_frm);
+    let output_path = args.flag_output.as_ref().map(|o| Path::new(o)).unwrap_or(frm_path);
+    lyrics::retime_frm_file(frm_path, output_path, args.arg_from_fps, args.arg_to_fps)
+        .unwrap_or_else(|e| {
+            error!("Failed to retime {} : {}", frm_path.display(), e);
+            std::process::exit(1);
+        });
+}
+
+fn check(args: &CmdArgs, config: &config::Config) {
+    let ttf_context = sdl2_ttf::init().unwrap();
+    let font_path = Path::new(displayer::FONT_PATH);
+    // lines are measured the way the player draws them
+    let sdf_font;
+    let gl_measure;
+    let fonts;
+    let measure: &check::TextMeasure = if config.gl_text {
+        sdf_font = gltext::load_font(font_path, &ttf_context).expect("Failed to load font");
+        gl_measure = check::GlMeasure(&sdf_font);
+        &gl_measure
+    } else {
+        // lines are only measured, outlines are not needed
+        fonts = displayer::FontList::new(font_path, &ttf_context, &[], 1)
+                    .expect("Failed to load font");
+        &fonts
+    };
+    let mut errors = 0;
+    for file in &args.arg_lyr {
+        let (lyr_path, frm_path) = lyrics::sidecar_paths(Path::new(file));
+        for diagnostic in check::check_song(&lyr_path, &frm_path, measure, config) {
+            if diagnostic.severity == check::Severity::Error {
+                errors += 1;
+            }
+            println!("{}", diagnostic);
+        }
+    }
+    if errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Draws the lyrics at the requested times in memory, and writes them as
+/// images or compares them to the images of a previous run
+fn frames(args: &CmdArgs, config: &config::Config) {
+    let size = headless::parse_size(&args.flag_size).unwrap_or_else(|| {
+        error!("Invalid frame size {}", args.flag_size);
+        std::process::exit(1);
+    });
+    let video_path = Path::new(&args.arg_video);
+    let mpv = headless::null_mpv().unwrap_or_else(|e| {
+        error!("Failed to initialize mpv : {}", e);
+        std::process::exit(1);
+    });
+    headless::load_file(&mpv, video_path).unwrap_or_else(|e| {
+        error!("Failed to load {} : {}", video_path.display(), e);
+        std::process::exit(1);
+    });
+    let variants = load_lyrics(&mpv, video_path, video_fps(&mpv)).unwrap_or_else(|e| {
+        error!("No lyrics loaded : {}", e);
+        std::process::exit(1);
+    });
+    let song = &variants.primary().song;
+    let offset = settings::SongSettings::load(video_path).offset;
+    let renderer = headless::software_renderer(size).expect("Failed to create renderer");
+    let mut displayer = displayer::Displayer::new(renderer, config)
+                            .expect("Failed to create displayer");
+    displayer.set_video_area(layout::video_area(&mpv, size));
+    let mut themes = config.themes.clone();
+    themes.set_song_theme(song.metadata.theme.as_ref().map(|name| &**name));
+    displayer.set_theme(themes.current(&song.metadata));
+
+    let stem = video_path.file_stem().map(|stem| stem.to_string_lossy().into_owned())
+                         .unwrap_or_default();
+    let mut mismatches = 0;
+    for &time in &args.arg_time {
+        displayer.clear();
+        let lyrics_time = cmp::max(0, time as i64 - offset as i64) as u32;
+        display_overlay(&mut displayer, song, lyrics_time, config);
+        displayer.render();
+        let frame = displayer.sdl_renderer().surface().unwrap();
+        let name = format!("{}-{}.bmp", stem, time);
+        if let Some(ref directory) = args.flag_compare {
+            let golden_path = Path::new(directory).join(&name);
+            let result = sdl2::surface::Surface::load_bmp(&golden_path).and_then(|golden| {
+                headless::differing_pixels(frame, &golden, GOLDEN_TOLERANCE)
+            });
+            match result {
+                Ok(0) => println!("{} : ok", name),
+                Ok(count) => {
+                    println!("{} : {} pixels differ", name, count);
+                    mismatches += 1;
+                }
+                Err(e) => {
+                    println!("{} : {}", name, e);
+                    mismatches += 1;
+                }
+            }
+        } else {
+            let directory = args.flag_output.as_ref().map(|o| &**o).unwrap_or(".");
+            let path = Path::new(directory).join(&name);
+            frame.save_bmp(&path).unwrap_or_else(|e| {
+                error!("Failed to write {} : {}", path.display(), e);
+                std::process::exit(1);
+            });
+        }
+    }
+    if mismatches > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Plays a video frame after frame, at its own pace rather than the one of
+/// the clock, and encodes it with the lyrics drawn over it
+fn render(args: &CmdArgs, config: &config::Config) {
+    let size = headless::parse_size(&args.flag_size).unwrap_or_else(|| {
+        error!("Invalid frame size {}", args.flag_size);
+        std::process::exit(1);
+    });
+    let video_path = Path::new(&args.arg_video);
+    let mpv = headless::null_mpv().unwrap_or_else(|e| {
+        error!("Failed to initialize mpv : {}", e);
+        std::process::exit(1);
+    });
+    headless::load_file(&mpv, video_path).unwrap_or_else(|e| {
+        error!("Failed to load {} : {}", video_path.display(), e);
+        std::process::exit(1);
+    });
+    let fps = video_fps(&mpv);
+    let variants = load_lyrics(&mpv, video_path, fps).unwrap_or_else(|e| {
+        error!("No lyrics loaded : {}", e);
+        std::process::exit(1);
+    });
+    let song = &variants.primary().song;
+    let offset = settings::SongSettings::load(video_path).offset;
+    let renderer = headless::software_renderer(size).expect("Failed to create renderer");
+    let mut displayer = displayer::Displayer::new(renderer, config)
+                            .expect("Failed to create displayer");
+    displayer.set_video_area(layout::video_area(&mpv, size));
+    let mut themes = config.themes.clone();
+    themes.set_song_theme(song.metadata.theme.as_ref().map(|name| &**name));
+    displayer.set_theme(themes.current(&song.metadata));
+
+    // files without video get a black background for their whole duration
+    let mut decoder = if tracks::is_audio_only(&tracks::track_list(&mpv)) {
+        None
+    } else {
+        Some(render::Decoder::spawn(video_path, size, fps).unwrap_or_else(|e| {
+            error!("Failed to run ffmpeg : {}", e);
+            std::process::exit(1);
+        }))
+    };
+    // frame times follow the frames ffmpeg decodes
+    let fps = decoder.as_ref().map(|decoder| decoder.fps()).unwrap_or(fps);
+    let duration = mpv.get_property::<f64>("duration").unwrap_or(0.0);
+    let frame_count = (duration * fps) as u64;
+    let output_path = Path::new(&args.arg_output);
+    let mut encoder = render::Encoder::spawn(output_path, video_path, size, fps)
+                          .unwrap_or_else(|e| {
+                              error!("Failed to run ffmpeg : {}", e);
+                              std::process::exit(1);
+                          });
+    let mut frame = 0;
+    loop {
+        let decoded = match decoder {
+            Some(ref mut decoder) => {
+                let surface = displayer.sdl_renderer_mut().surface_mut().unwrap();
+                decoder.read_frame(surface)
+            }
+            None => {
+                displayer.clear();
+                Ok(frame < frame_count)
+            }
+        };
+        match decoded {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                error!("Failed to decode frame {} : {}", frame, e);
+                // the frames encoded so far are still written
+                if let Err(e) = encoder.finish() {
+                    error!("Failed to wait for ffmpeg : {}", e);
+                }
+                std::process::exit(1);
+            }
+        }
+        let time = (frame as f64 * 1000.0 / fps) as u32;
+        let lyrics_time = cmp::max(0, time as i64 - offset as i64) as u32;
+        display_overlay(&mut displayer, song, lyrics_time, config);
+        displayer.render();
+        if let Err(e) = encoder.write_frame(displayer.sdl_renderer().surface().unwrap()) {
+            error!("Failed to encode frame : {}", e);
+            std::process::exit(1);
+        }
+        frame += 1;
+        if frame % (10 * fps as u64 + 1) == 0 {
+            info!("Rendered {}", osd::format_time(time));
+        }
+    }
+    match encoder.finish() {
+        Ok(status) if status.success() => info!("Rendered {} frames", frame),
+        Ok(status) => {
+            error!("ffmpeg failed : {}", status);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            error!("Failed to wait for ffmpeg : {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
     env_logger::init().unwrap();
 
@@ -72,6 +509,53 @@ fn main() {
         .and_then(|d| d.decode())
         .unwrap_or_else(|e| e.exit());
 
+    let config = match args.flag_config {
+        Some(ref path) => {
+            config::Config::load(Path::new(path)).unwrap_or_else(|e| {
+                error!("Failed to load configuration {} : {}", path, e);
+                std::process::exit(1);
+            })
+        }
+        None => config::Config::default(),
+    };
+
+    if args.cmd_retime {
+        retime(&args);
+        return;
+    }
+    if args.cmd_check {
+        check(&args, &config);
+        return;
+    }
+    if args.cmd_frames {
+        frames(&args, &config);
+        return;
+    }
+    if args.cmd_render {
+        render(&args, &config);
+        return;
+    }
+    let mut queue_items: Vec<queue::QueueItem> = args.arg_file
+                                                     .iter()
+                                                     .map(|file| {
+                                                         queue::QueueItem {
+                                                             path: PathBuf::from(file),
+                                                             singer: None,
+                                                         }
+                                                     })
+                                                     .collect();
+    if let Some(ref path) = args.flag_queue {
+        queue_items.extend(queue::Queue::load(Path::new(path)).unwrap_or_else(|e| {
+            error!("Failed to load queue {} : {}", path, e);
+            std::process::exit(1);
+        }));
+    }
+    let mut queue = queue::Queue::new(queue_items);
+    if queue.is_empty() {
+        error!("Nothing to play");
+        std::process::exit(1);
+    }
+
     let sdl_context = sdl2::init().unwrap();
 
     let mut video_subsystem = sdl_context.video().unwrap();
@@ -100,68 +584,495 @@ fn main() {
         .index(opengl_driver)
         .build()
         .expect("Failed to create renderer with given parameters");
-    let mut displayer = displayer::Displayer::new(renderer).expect("Failed to create displayer");
+    // the lyrics are drawn with OpenGL in the context of the renderer
+    gl::load_with(|name| video_subsystem.gl_get_proc_address(name) as *const _);
+    let mut displayer = displayer::Displayer::new(renderer, &config).expect("Failed to create displayer");
     displayer.sdl_renderer()
         .window()
         .expect("Failed to extract window from displayer")
         .gl_set_context_to_current();
+    // audience and singers see the same song at the same time
+    let mut stage_monitor = config.stage_monitor.and_then(|display| {
+        monitor::open_window(&video_subsystem, display)
+            .map_err(|e| warn!("Failed to open the stage monitor : {}", e))
+            .ok()
+            .and_then(|renderer| {
+                displayer::Displayer::new(renderer, &config)
+                    .map_err(|_| warn!("Failed to create the stage monitor displayer"))
+                    .ok()
+            })
+    });
 
     let mpv = mpv::Mpv::init().expect("Error while initializing MPV");
     let mpv_gl = get_mpv_gl(&mpv, &mut video_subsystem);
     mpv.set_option("vo", "opengl-cb").expect("Error setting vo option to opengl-cb");
+    // embedded subtitles are displayed by the player itself, not by mpv
     mpv.set_option("sid", "no").expect("Error setting custom option sid to false");
-    mpv.command(&["loadfile", &args.arg_file as &str]).expect("Error loading file");
+    // the player goes on between two songs of the queue
+    mpv.set_option("idle", "yes").expect("Error setting idle option");
+    // the main loop sleeps until mpv has an event or a new frame
+    let wakeup_event = wakeup::register().unwrap_or_else(|e| {
+        error!("Failed to register the wakeup event : {}", e);
+        std::process::exit(1);
+    });
+    mpv.set_wakeup_callback(Some(wakeup::wake_up), wakeup_event);
+    mpv_gl.set_update_callback(Some(wakeup::wake_up), wakeup_event);
+    let mut video_path = queue.current().unwrap().path.clone();
+    mpv.command(&["loadfile", &*video_path.to_string_lossy()]).expect("Error loading file");
+
+    let (mut lyr_path, mut frm_path) = lyrics::sidecar_paths(&video_path);
+    let mut variants: Option<variants::Variants> = None;
+    let mut romaji_song = None;
+    let mut romaji_mode = romaji::RomajiMode::Off;
+    let mut editor = None;
+    let mut fps = lyrics::DEFAULT_FPS;
+    let mut song_settings = settings::SongSettings::load(&video_path);
+    let mut lyrics_watcher = None;
+    // lyrics being read from the subtitles of a video without lyr file
+    let mut extraction: Option<subtitles::Extraction> = None;
+    let mut title_card: Option<(Vec<String>, Instant)> = None;
+    let mut title = String::new();
+    let mut song_metadata = lyrics::Metadata::default();
+    let mut themes = config.themes.clone();
+    // metadata of the next song while waiting for it
+    let mut interlude: Option<(lyrics::Metadata, Instant)> = None;
+    let mut background: Option<background::Background> = None;
+    // the frame is read back once everything is drawn over it
+    let mut screenshot_requested = false;
+    let csv_path = args.flag_diagnostics.as_ref().map(|path| Path::new(path));
+    let mut diagnostics = diagnostics::Diagnostics::new(csv_path).unwrap_or_else(|e| {
+        error!("Failed to create diagnostics file : {}", e);
+        std::process::exit(1);
+    });
 
     let mut event_pump = sdl_context.event_pump().expect("Failed to create event_pump");
+    let mut timeout = 0;
+    // whether the loop waits for events rather than for the next frame
+    let mut idle = false;
     'running: loop {
-        for event in event_pump.poll_iter() {
+        // the user events pushed by mpv only wake the loop up
+        let mut events: Vec<Event> = event_pump.wait_event_timeout(timeout).into_iter().collect();
+        events.extend(event_pump.poll_iter());
+        let time = (mpv.get_property::<f64>("time-pos").unwrap_or(0.0) * 1000.0) as u32;
+        for event in events {
             match event {
                 Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     break 'running
                 },
                 Event::KeyDown { keycode: Some(Keycode::Space),repeat: false, .. } => {
-                    let instant = time::Instant::now() ;
-                    match mpv.get_property_string("pause") {
-                        "yes" => {mpv.set_property("pause","no").expect("Failed to pause player");},
-                        "no" => {mpv.set_property("pause","yes").expect("Failed to unpause player");},
+                    let status = match &*mpv.get_property_string("pause") {
+                        "yes" => {mpv.set_property("pause","no").expect("Failed to pause player"); "Playing"},
+                        "no" => {mpv.set_property("pause","yes").expect("Failed to unpause player"); "Paused"},
                         _ => {panic!("unexpected answer from get_property_string");}
-                    }
-                    println!("Duration of pause / unpause : {},{}",instant.elapsed().as_secs(),instant.elapsed().subsec_nanos());
-                },
-                Event::KeyDown { keycode: Some(Keycode::Kp9), repeat: false, .. } => {mpv.set_property_async("speed",0.9).unwrap();},
-                Event::KeyDown { keycode: Some(Keycode::Kp8), repeat: false, .. } => {mpv.set_property_async("speed",0.8).unwrap();},
-                Event::KeyDown { keycode: Some(Keycode::Kp7), repeat: false, .. } => {mpv.set_property_async("speed",0.7).unwrap();},
-                Event::KeyDown { keycode: Some(Keycode::Kp6), repeat: false, .. } => {mpv.set_property_async("speed",0.6).unwrap();},
-                Event::KeyDown { keycode: Some(Keycode::Kp5), repeat: false, .. } => {mpv.set_property_async("speed",0.5).unwrap();},
-                Event::KeyDown { keycode: Some(Keycode::Kp4), repeat: false, .. } => {mpv.set_property_async("speed",0.4).unwrap();},
-                Event::KeyDown { keycode: Some(Keycode::Kp3), repeat: false, .. } => {mpv.set_property_async("speed",0.3).unwrap();},
-                Event::KeyDown { keycode: Some(Keycode::Kp2), repeat: false, .. } => {mpv.set_property_async("speed",0.2).unwrap();},
-                Event::KeyDown { keycode: Some(Keycode::Kp1), repeat: false, .. } => {mpv.set_property_async("speed",0.1).unwrap();},
-                Event::KeyDown { keycode: Some(Keycode::Kp0), repeat: false, .. } => {mpv.set_property_async("speed",1.0).unwrap();},
+                    };
+                    displayer.notify(format!("{}\n{}", status, playback_position(&mpv, time)));
+                },
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } if keypad_speed(keycode).is_some() => {
+                    let speed = keypad_speed(keycode).unwrap();
+                    mpv.set_property_async("speed", speed).unwrap();
+                    displayer.notify(format!("Speed {}x", speed));
+                },
                 Event::KeyDown { keycode: Some(Keycode::F), repeat: false, .. } => {
-                    let instant = time::Instant::now() ;
-                    if (displayer.sdl_renderer().window().unwrap().window_flags() &
-                        (SDL_WindowFlags::SDL_WINDOW_FULLSCREEN as u32)) != 0 {
+                    let fullscreen = (displayer.sdl_renderer().window().unwrap().window_flags() &
+                                      (SDL_WindowFlags::SDL_WINDOW_FULLSCREEN as u32)) != 0;
+                    if fullscreen {
                         displayer.sdl_renderer_mut().window_mut().unwrap().set_fullscreen(FullscreenType::Off)
                     } else {
                         displayer.sdl_renderer_mut().window_mut().unwrap().set_fullscreen(FullscreenType::Desktop)
                     }
                     .expect("Failed to change fullscreen parameter of mpv");
-                    println!("Duration of fullscreen : {},{}",instant.elapsed().as_secs(),instant.elapsed().subsec_nanos());
-                }
+                    let mode = if fullscreen { "Windowed" } else { "Fullscreen" };
+                    displayer.notify(mode.to_string());
+                },
+                Event::KeyDown { keycode: Some(Keycode::I), repeat: false, .. } => {
+                    let title = mpv.try_get_property_string("media-title")
+                                   .unwrap_or_else(|| video_path.to_string_lossy().into_owned());
+                    displayer.notify(format!("{}\n{}", title, playback_position(&mpv, time)));
+                },
+                Event::KeyDown { keycode: Some(Keycode::C), repeat: false, .. } => {
+                    themes.cycle();
+                    let name = apply_theme(&themes,
+                                           &song_metadata,
+                                           &mut displayer,
+                                           &mut stage_monitor);
+                    displayer.notify(format!("Theme : {}", name));
+                },
+                Event::KeyDown { keycode: Some(Keycode::F3), repeat: false, .. } => {
+                    diagnostics.visible = !diagnostics.visible;
+                },
+                Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } => {
+                    screenshot_requested = true;
+                },
+                Event::KeyDown { keycode: Some(keycode @ Keycode::KpPlus), .. } |
+                Event::KeyDown { keycode: Some(keycode @ Keycode::KpMinus), .. } => {
+                    song_settings.offset += if keycode == Keycode::KpPlus {
+                        OFFSET_STEP
+                    } else {
+                        -OFFSET_STEP
+                    };
+                    info!("Lyrics offset : {} ms", song_settings.offset);
+                    song_settings.save(&video_path)
+                                 .unwrap_or_else(|e| warn!("Failed to save song settings : {}", e));
+                },
+                Event::KeyDown { keycode: Some(Keycode::R), repeat: false, .. } => {
+                    romaji_mode = romaji_mode.next();
+                    info!("Romaji display : {:?}", romaji_mode);
+                    let mode = match romaji_mode {
+                        romaji::RomajiMode::Off => "Romaji off",
+                        romaji::RomajiMode::Only => "Romaji only",
+                        romaji::RomajiMode::Alongside => "Romaji alongside",
+                    };
+                    displayer.notify(mode.to_string());
+                },
+                Event::KeyDown { keycode: Some(Keycode::V), repeat: false, .. } => {
+                    if let Some(ref mut variants) = variants {
+                        variants.cycle_primary();
+                        romaji_song = Some(romaji::romanize_song(&variants.primary().song));
+                        displayer.notify(format!("Lyrics : {}", variants.primary().name));
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::T), repeat: false, .. } => {
+                    if let Some(ref mut variants) = variants {
+                        variants.cycle_secondary();
+                        let name = variants.secondary().map(|v| &*v.name).unwrap_or("none");
+                        displayer.notify(format!("Secondary lyrics : {}", name));
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Return), repeat: false, .. } => {
+                    if let Some(ref mut editor) = editor {
+                        editor.key_down(time);
+                    }
+                },
+                Event::KeyUp { keycode: Some(Keycode::Return), .. } => {
+                    if let Some(ref mut editor) = editor {
+                        editor.key_up(time);
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                    if let Some(ref mut editor) = editor {
+                        editor.back();
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::S), repeat: false, .. } => {
+                    if let Some(ref mut editor) = editor {
+                        save_editor(editor, fps, &mut displayer);
+                    }
+                },
                 _ => {}
             }
         }
-        while let Some(_) = mpv.wait_event() {
-            // do something with the events
-            // but it's kind of useless
-            // it's still necessary to empty the event pool
+        while let Some(event) = mpv.wait_event() {
+            match event.event_id {
+                Enum_mpv_event_id::MPV_EVENT_FILE_LOADED => {
+                    // the frame rate is only known once the file is loaded
+                    fps = video_fps(&mpv);
+                    info!("Video frame rate : {} fps", fps);
+                    if args.flag_editor {
+                        // timings are only started from scratch when there are none,
+                        // an unreadable frm file must not be overwritten
+                        let loaded = if frm_path.exists() {
+                            lyrics::Song::load(&lyr_path, &frm_path, fps)
+                        } else {
+                            lyrics::Song::load_untimed(&lyr_path)
+                        };
+                        match loaded {
+                            Ok(song) => editor = Some(editor::Editor::new(song, &frm_path)),
+                            Err(e) => {
+                                error!("Failed to load lyrics for the editor : {}", e);
+                                displayer.notify(format!("Editor disabled : {}", e));
+                            }
+                        }
+                    } else if !lyr_path.exists() {
+                        // ffmpeg reads the whole video, the lyrics come later
+                        info!("No lyr file, looking for lyrics in subtitle tracks");
+                        extraction = subtitles::find_track(&mpv).map(|track| {
+                            subtitles::Extraction::start(&video_path, track, wakeup_event)
+                        });
+                        if extraction.is_none() {
+                            warn!("No lyrics loaded : no lyr file nor subtitle track found");
+                        }
+                    } else {
+                        variants = load_lyrics(&mpv, &video_path, fps)
+                                       .map_err(|e| warn!("No lyrics loaded : {}", e))
+                                       .ok();
+                        if let Some(ref mut variants) = variants {
+                            if let Some(ref name) = config.secondary_lyrics {
+                                variants.secondary = variants.find(name);
+                            }
+                            romaji_song = Some(romaji::romanize_song(&variants.primary().song));
+                            if !variants.files.is_empty() {
+                                lyrics_watcher = watcher::watch_lyrics(&variants.files);
+                            }
+                        }
+                    }
+                    let metadata = match (&editor, &variants) {
+                        (&Some(ref editor), _) => editor.song().metadata.clone(),
+                        (_, &Some(ref variants)) => variants.primary().song.metadata.clone(),
+                        _ => lyrics::Metadata::default(),
+                    };
+                    title_card = Some((cards::title_card(&metadata, queue.current().unwrap()),
+                                       Instant::now()));
+                    title = cards::title(&metadata, &video_path);
+                    themes.set_song_theme(metadata.theme.as_ref().map(|name| &**name));
+                    apply_theme(&themes, &metadata, &mut displayer, &mut stage_monitor);
+                    song_metadata = metadata;
+                    let file_tracks = tracks::track_list(&mpv);
+                    background = background::Background::choose(config.audio_background,
+                                                                &file_tracks,
+                                                                config.background_images
+                                                                      .as_ref()
+                                                                      .map(|d| Path::new(d)),
+                                                                config.slideshow_interval);
+                    if let Some(ref mut background) = background {
+                        background.start(&mpv, &file_tracks, &config.visualizer);
+                    }
+                },
+                Enum_mpv_event_id::MPV_EVENT_END_FILE => {
+                    if let Some(ref mut editor) = editor {
+                        if editor.is_modified() {
+                            save_editor(editor, fps, &mut displayer);
+                        }
+                    }
+                    editor = None;
+                    variants = None;
+                    extraction = None;
+                    romaji_song = None;
+                    lyrics_watcher = None;
+                    title_card = None;
+                    song_metadata = lyrics::Metadata::default();
+                    themes.set_song_theme(None);
+                    apply_theme(&themes, &song_metadata, &mut displayer, &mut stage_monitor);
+                    if let Some(ref background) = background {
+                        background.stop(&mpv);
+                    }
+                    background = None;
+                    if queue.advance() {
+                        let metadata = cards::read_metadata(&queue.current().unwrap().path);
+                        title = cards::title(&metadata, &queue.current().unwrap().path);
+                        interlude = Some((metadata, Instant::now()));
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        let extracted = extraction.as_ref().and_then(|extraction| extraction.result());
+        if let Some(extracted) = extracted {
+            extraction = None;
+            match extracted {
+                Some(song) => {
+                    info!("Lyrics read from the subtitles");
+                    romaji_song = Some(romaji::romanize_song(&song));
+                    title = cards::title(&song.metadata, &video_path);
+                    song_metadata = song.metadata.clone();
+                    variants = Some(variants::Variants::single(song));
+                },
+                None => warn!("No lyrics loaded : no subtitle track could be read"),
+            }
+        }
+
+        if lyrics_watcher.as_mut().map(|watcher| watcher.changed()).unwrap_or(false) {
+            // the lyrics are swapped in place, mpv keeps playing. The lyr file
+            // may be missing for a moment while it is saved : the current
+            // lyrics are kept, never replaced by the subtitles.
+            match variants::Variants::load(&video_path, fps) {
+                Ok(mut reloaded) => {
+                    info!("Lyrics reloaded");
+                    if let Some(ref previous) = variants {
+                        reloaded.keep_selection(previous);
+                    }
+                    romaji_song = Some(romaji::romanize_song(&reloaded.primary().song));
+                    // the theme directives may have been edited too
+                    song_metadata = reloaded.primary().song.metadata.clone();
+                    themes.set_song_theme(song_metadata.theme.as_ref().map(|name| &**name));
+                    apply_theme(&themes, &song_metadata, &mut displayer, &mut stage_monitor);
+                    variants = Some(reloaded);
+                    displayer.notify("Lyrics reloaded".to_string());
+                },
+                Err(e) => {
+                    warn!("Failed to reload lyrics : {}", e);
+                    displayer.notify(format!("Lyrics error : {}", e));
+                },
+            }
+        }
+
+        if let Some(ref mut background) = background {
+            background.update(&mpv);
+        }
+        let interlude_over = interlude.as_ref()
+                                      .map(|&(_, since)| elapsed_ms(since) >= config.interlude.duration)
+                                      .unwrap_or(false);
+        if interlude_over {
+            interlude = None;
+            video_path = queue.current().unwrap().path.clone();
+            let (lyr, frm) = lyrics::sidecar_paths(&video_path);
+            lyr_path = lyr;
+            frm_path = frm;
+            song_settings = settings::SongSettings::load(&video_path);
+            mpv.command(&["loadfile", &*video_path.to_string_lossy()]).expect("Error loading file");
         }
 
         let (width, height) = displayer.sdl_renderer().window().unwrap().size();
+        let draw_start = Instant::now();
+        // mpv leaves its own program, textures and blending behind
+        let sdl_gl_state = gltext::GlState::save();
         mpv_gl.draw(0, width as i32, -(height as i32)).expect("Failed to draw ");
-        displayer.display("0123456789ABCDEF0123456789abcdef0123456789ABCDEF");
+        sdl_gl_state.restore();
+        let mpv_draw = diagnostics::ms(draw_start.elapsed());
+        let overlay_start = Instant::now();
+        displayer.set_video_area(layout::video_area(&mpv, (width, height)));
+        displayer.dim_background();
+        if let Some(ref editor) = editor {
+            if let Some((line, timed)) = editor.current_line() {
+                displayer.display_line(editor.song(),
+                                       line,
+                                       timed,
+                                       1.0,
+                                       0,
+                                       &animation::Appearance::visible());
+            }
+        } else if let Some(ref variants) = variants {
+            let song = &variants.primary().song;
+            let lyrics_time = cmp::max(0, time as i64 - song_settings.offset as i64) as u32;
+            let transitions = &config.transitions;
+            match (romaji_mode, romaji_song.as_ref()) {
+                (romaji::RomajiMode::Only, Some(romaji_song)) => {
+                    display_lyrics(&mut displayer, romaji_song, lyrics_time, 0, transitions);
+                },
+                (romaji::RomajiMode::Alongside, Some(romaji_song)) => {
+                    display_lyrics(&mut displayer, song, lyrics_time, 0, transitions);
+                    display_lyrics(&mut displayer, romaji_song, lyrics_time, 1, transitions);
+                },
+                _ => display_lyrics(&mut displayer, song, lyrics_time, 0, transitions),
+            }
+            if let Some(secondary) = variants.secondary() {
+                let row = if romaji_mode == romaji::RomajiMode::Alongside {
+                    2
+                } else {
+                    1
+                };
+                for index in song.visible_line_indexes(lyrics_time,
+                                                       transitions.linger(),
+                                                       transitions.lead()) {
+                    let line = &song.lines[index];
+                    let appearance = match transitions.appearance(line, lyrics_time) {
+                        Some(appearance) => appearance,
+                        None => continue,
+                    };
+                    if let Some(secondary_line) = secondary.song.lines.get(index) {
+                        // untimed translations are never highlighted
+                        let sung = secondary_line.syllables
+                                                 .iter()
+                                                 .filter(|s| s.end > 0 && s.begin <= lyrics_time)
+                                                 .count();
+                        displayer.display_secondary_line(song,
+                                                         line,
+                                                         &secondary.song,
+                                                         secondary_line,
+                                                         sung,
+                                                         row,
+                                                         &appearance);
+                    }
+                }
+            }
+            if let Some((line, remaining)) = song.countdown(lyrics_time,
+                                                            config.countdown_gap,
+                                                            config.countdown_duration) {
+                displayer.display_countdown(song, line, remaining);
+            }
+        }
+        if let Some((ref metadata, since)) = interlude {
+            let remaining = config.interlude.duration.saturating_sub(elapsed_ms(since));
+            let lines = cards::up_next_card(metadata,
+                                            queue.current().unwrap(),
+                                            (remaining + 999) / 1000);
+            displayer.display_card(&lines, &config.interlude);
+        }
+        if title_card.as_ref().map(|&(_, since)| elapsed_ms(since) >= config.title_card.duration)
+                     .unwrap_or(false) {
+            title_card = None;
+        }
+        if let Some((ref lines, _)) = title_card {
+            displayer.display_card(lines, &config.title_card);
+        }
+        if song_settings.offset != 0 {
+            displayer.display_offset(song_settings.offset);
+        }
+        displayer.display_osd();
+        if diagnostics.visible {
+            displayer.display_diagnostics(&diagnostics.lines());
+        }
+        let overlay = diagnostics::ms(overlay_start.elapsed());
+        if screenshot_requested {
+            screenshot_requested = false;
+            let directory = config.screenshot_directory.as_ref().map(|d| &**d).unwrap_or(".");
+            let path = screenshot::file_path(Path::new(directory), &video_path);
+            let saved = displayer.screenshot()
+                                 .and_then(|shot| shot.save_png(&path).map_err(|e| e.to_string()));
+            match saved {
+                Ok(()) => {
+                    info!("Screenshot saved to {}", path.display());
+                    displayer.notify(format!("Screenshot saved\n{}", path.display()));
+                },
+                Err(e) => {
+                    warn!("Failed to save screenshot {} : {}", path.display(), e);
+                    displayer.notify(format!("Screenshot failed : {}", e));
+                },
+            }
+        }
+        let swap_start = Instant::now();
         displayer.render();
+        let swap = diagnostics::ms(swap_start.elapsed());
+        if let Err(e) = mpv_gl.report_flip(0) {
+            warn!("Failed to report flip to mpv : {}", e);
+        }
+        if diagnostics.enabled() {
+            diagnostics.record(&mpv,
+                               time,
+                               diagnostics::Sample {
+                                   mpv_draw: mpv_draw,
+                                   overlay: overlay,
+                                   swap: swap,
+                                   idle: idle,
+                                   ..Default::default()
+                               });
+        }
+
+        if let Some(ref mut stage_monitor) = stage_monitor {
+            stage_monitor.clear();
+            if let Some(ref variants) = variants {
+                let song = &variants.primary().song;
+                let lyrics_time = cmp::max(0, time as i64 - song_settings.offset as i64) as u32;
+                let view = monitor::stage_view(song, lyrics_time, STAGE_UPCOMING_LINES);
+                stage_monitor.display_stage(song, &view);
+            }
+            let mut footer = monitor::queue_lines(&queue, &title, STAGE_QUEUE_LENGTH);
+            footer.push(playback_position(&mpv, time));
+            stage_monitor.display_stage_footer(&footer);
+            stage_monitor.render();
+        }
+
+        // lyrics move while the media plays, cards and the OSD only change
+        // when they time out or the interlude countdown ticks
+        let playing = !mpv.get_flag("core-idle").unwrap_or(true);
+        let animated = playing && (editor.is_some() || variants.is_some());
+        let title_card_remaining = title_card.as_ref().map(|&(_, since)| {
+            config.title_card.duration.saturating_sub(elapsed_ms(since))
+        });
+        let interlude_tick = interlude.as_ref().map(|&(_, since)| {
+            config.interlude.duration.saturating_sub(elapsed_ms(since)) % 1000 + 1
+        });
+        timeout = redraw_timeout(animated,
+                                 fps,
+                                 &[displayer.osd_remaining(), title_card_remaining, interlude_tick]);
+        idle = !animated;
+    }
+    if let Some(ref mut editor) = editor {
+        if editor.is_modified() {
+            if let Err(e) = editor.save(fps) {
+                error!("Failed to write frm file : {}", e);
+            }
+        }
     }
     info!("Exiting software peacefully");
 }
@@ -0,0 +1,93 @@
+use sdl2::render::Renderer;
+use sdl2::VideoSubsystem;
+
+use cards;
+use lyrics::{Line, Song};
+use queue::Queue;
+
+/// What the singers see on the stage monitor at a given time
+pub struct StageView<'a> {
+    /// Line being sung, with its number of sung syllables and the progress
+    /// of the last of them, as returned by `Line::progress`
+    pub current: Option<(&'a Line, usize, f32)>,
+    /// Lines coming after the current one
+    pub upcoming: Vec<&'a Line>,
+    /// Milliseconds before the next line begins, while no line is sung
+    pub next_entry: Option<u32>,
+}
+
+/// Computes the stage view of `song` at `time`, with at most
+/// `upcoming_count` upcoming lines
+pub fn stage_view(song: &Song, time: u32, upcoming_count: usize) -> StageView {
+    let current = song.lines
+                      .iter()
+                      .position(|line| line.begin() <= time && time <= line.end());
+    let upcoming: Vec<&Line> = song.lines
+                                   .iter()
+                                   .enumerate()
+                                   .filter(|&(index, line)| {
+                                       line.begin() > time && Some(index) != current
+                                   })
+                                   .map(|(_, line)| line)
+                                   .take(upcoming_count)
+                                   .collect();
+    let next_entry = match current {
+        Some(_) => None,
+        None => upcoming.first().map(|line| line.begin() - time),
+    };
+    StageView {
+        current: current.map(|index| {
+            let line = &song.lines[index];
+            let (sung, progress) = line.progress(time);
+            (line, sung, progress)
+        }),
+        upcoming: upcoming,
+        next_entry: next_entry,
+    }
+}
+
+/// Lines about the queue : the song being played, titled `title`, and the
+/// `count` following ones
+pub fn queue_lines(queue: &Queue, title: &str, count: usize) -> Vec<String> {
+    let with_singer = |title: String, singer: &Option<String>| {
+        match *singer {
+            Some(ref singer) => format!("{} ({})", title, singer),
+            None => title,
+        }
+    };
+    let mut lines = Vec::new();
+    if let Some(item) = queue.current() {
+        lines.push(format!("Now : {}", with_singer(title.to_string(), &item.singer)));
+    }
+    // the metadata of the following songs is only read when they start
+    for item in queue.upcoming().iter().take(count) {
+        let title = cards::title(&Default::default(), &item.path);
+        lines.push(format!("Then : {}", with_singer(title, &item.singer)));
+    }
+    lines
+}
+
+/// Opens the stage monitor window, full screen on display `display` if it
+/// exists, in a window otherwise. It is drawn by the software renderer,
+/// leaving the OpenGL context to the main window.
+pub fn open_window(video_subsystem: &VideoSubsystem,
+                   display: i32)
+                   -> Result<Renderer<'static>, String> {
+    let mut builder = match video_subsystem.display_bounds(display) {
+        Ok(bounds) => {
+            let mut builder = video_subsystem.window("Toyunda Player - Stage",
+                                                     bounds.width(),
+                                                     bounds.height());
+            builder.position(bounds.x(), bounds.y()).fullscreen_desktop();
+            builder
+        }
+        Err(e) => {
+            warn!("No display {} for the stage monitor : {}", display, e);
+            let mut builder = video_subsystem.window("Toyunda Player - Stage", 960, 540);
+            builder.position_centered().resizable();
+            builder
+        }
+    };
+    let window = try!(builder.build().map_err(|e| e.to_string()));
+    window.renderer().software().build().map_err(|e| e.to_string())
+}
@@ -53,11 +53,11 @@ impl MpvFormatProperty for f64 {
     }
 }
 
-impl MpvFormatProperty for bool {
+impl MpvFormatProperty for i64 {
     fn to_mpv_format(&mut self) -> MpvFormat {
         let ptr = self as *mut _ as *mut libc::c_void;
         MpvFormat::RawMpvFormat {
-            format: Enum_mpv_format::MPV_FORMAT_FLAG,
+            format: Enum_mpv_format::MPV_FORMAT_INT64,
             data: ptr,
         }
     }
@@ -113,8 +113,13 @@ impl Mpv {
     }
 
     pub fn wait_event(&self) -> Option<Struct_mpv_event> {
+        self.wait_event_timeout(0.0)
+    }
+
+    /// Waits up to `timeout` seconds for an event, forever if negative
+    pub fn wait_event_timeout(&self, timeout: f64) -> Option<Struct_mpv_event> {
         let event = unsafe {
-            let ptr = mpv_wait_event(self.handle, 0.0);
+            let ptr = mpv_wait_event(self.handle, timeout);
             if ptr.is_null() {
                 panic!("Unexpected null ptr from mpv_wait_event");
             }
@@ -126,6 +131,14 @@ impl Mpv {
         }
     }
 
+    /// Sets a function mpv calls, from any of its threads, when it has new
+    /// events. It must not call mpv, only wake up the thread handling them.
+    pub fn set_wakeup_callback(&self,
+                               callback: Option<unsafe extern "C" fn(*mut libc::c_void)>,
+                               data: *mut libc::c_void) {
+        unsafe { mpv_set_wakeup_callback(self.handle, callback, data) }
+    }
+
     pub fn set_property<T: MpvFormatProperty>(&self, property: &str, mut value: T) -> Result<()> {
         let format_struct: MpvFormat = value.to_mpv_format();
         let ret = match format_struct {
@@ -169,22 +182,53 @@ impl Mpv {
         ret_to_result(ret, ())
     }
 
-    pub fn get_property_string(&self, property: &str) -> &str {
+    pub fn get_property<T: MpvFormatProperty + Default>(&self, property: &str) -> Result<T> {
+        let mut value = T::default();
+        let ret = match value.to_mpv_format() {
+            MpvFormat::RawMpvFormat { format, data: ptr } => unsafe {
+                mpv_get_property(self.handle,
+                                 ffi::CString::new(property).unwrap().as_ptr(),
+                                 format,
+                                 ptr)
+            },
+            MpvFormat::Str(_) => return Err(Enum_mpv_error::MPV_ERROR_PROPERTY_FORMAT),
+        };
+        ret_to_result(ret, value)
+    }
+
+    /// Reads a yes/no property. mpv writes flags as C ints, which bool can't
+    /// hold.
+    pub fn get_flag(&self, property: &str) -> Result<bool> {
+        let mut value: libc::c_int = 0;
+        let ret = unsafe {
+            mpv_get_property(self.handle,
+                             ffi::CString::new(property).unwrap().as_ptr(),
+                             Enum_mpv_format::MPV_FORMAT_FLAG,
+                             &mut value as *mut _ as *mut libc::c_void)
+        };
+        ret_to_result(ret, value != 0)
+    }
+
+    pub fn get_property_string(&self, property: &str) -> String {
+        self.try_get_property_string(property).unwrap()
+    }
+
+    /// Returns None when the property doesn't exist or isn't available
+    pub fn try_get_property_string(&self, property: &str) -> Option<String> {
         unsafe {
             let ret = mpv_get_property_string(self.handle,
                                               ffi::CString::new(property)
                                                   .unwrap()
                                                   .as_ptr());
             if (!ret.is_null()) {
-                let ret_str = ffi::CStr::from_ptr(ret)
-                                  .to_str()
-                                  .unwrap();
+                let ret_string = ffi::CStr::from_ptr(ret)
+                                     .to_string_lossy()
+                                     .into_owned();
                 mpv_free(ret as *mut libc::c_void);
-                Some(ret_str)
+                Some(ret_string)
             } else {
                 None
             }
-            .unwrap()
         }
     }
 
@@ -252,10 +296,20 @@ impl OpenglContext {
         ret_to_result(ret, ())
     }
 
-    // pub fn report_flip(&self, time: i64) -> Result<()> {
-    //    let ret = unsafe { mpv_opengl_cb_report_flip(self.handle, time) };
-    //    ret_to_result(ret, ())
-    // }
+    /// Sets a function mpv calls, from any of its threads, when a new frame
+    /// is to be drawn. Like the wakeup callback, it must not call mpv.
+    pub fn set_update_callback(&self,
+                               callback: mpv_opengl_cb_update_fn,
+                               data: *mut libc::c_void) {
+        unsafe { mpv_opengl_cb_set_update_callback(self.handle, callback, data) }
+    }
+
+    /// Tells mpv the frame was displayed at `time`, in mpv_get_time_us
+    /// microseconds, 0 meaning now
+    pub fn report_flip(&self, time: i64) -> Result<()> {
+        let ret = unsafe { mpv_opengl_cb_report_flip(self.handle, time) };
+        ret_to_result(ret, ())
+    }
 }
 
 impl Drop for OpenglContext {
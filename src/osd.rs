@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+/// Corner, or center, of the window the OSD is displayed in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OsdPosition {
+    TopLeft,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OsdPosition {
+    pub fn parse(name: &str) -> Option<OsdPosition> {
+        match name {
+            "top-left" => Some(OsdPosition::TopLeft),
+            "top-right" => Some(OsdPosition::TopRight),
+            "center" => Some(OsdPosition::Center),
+            "bottom-left" => Some(OsdPosition::BottomLeft),
+            "bottom-right" => Some(OsdPosition::BottomRight),
+            _ => None,
+        }
+    }
+}
+
+/// On-screen display of the player state : a message, of one or several
+/// lines, shown for a while whatever the lyrics
+pub struct Osd {
+    pub position: OsdPosition,
+    timeout: Duration,
+    message: Option<(String, Instant)>,
+}
+
+impl Osd {
+    /// `timeout` is in milliseconds
+    pub fn new(position: OsdPosition, timeout: u32) -> Osd {
+        Osd {
+            position: position,
+            timeout: Duration::from_millis(timeout as u64),
+            message: None,
+        }
+    }
+
+    /// Replaces the current message
+    pub fn show(&mut self, text: String) {
+        self.message = Some((text, Instant::now()));
+    }
+
+    /// Milliseconds before the message times out, None without message
+    pub fn remaining(&self) -> Option<u32> {
+        match self.message {
+            Some((_, since)) => {
+                self.timeout.checked_sub(since.elapsed()).map(|remaining| {
+                    remaining.as_secs() as u32 * 1000 + remaining.subsec_nanos() / 1_000_000
+                })
+            }
+            None => None,
+        }
+    }
+
+    /// Returns the message if it has not timed out yet
+    pub fn message(&self) -> Option<&str> {
+        match self.message {
+            Some((ref text, since)) if since.elapsed() < self.timeout => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// Formats milliseconds as `m:ss`, or `h:mm:ss` past an hour
+pub fn format_time(time: u32) -> String {
+    let seconds = time / 1000;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
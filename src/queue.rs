@@ -0,0 +1,77 @@
+use std::cmp;
+use std::path::{Path, PathBuf};
+
+use lyrics;
+
+/// A song waiting to be played, with the name of whoever asked for it
+#[derive(Debug, Clone)]
+pub struct QueueItem {
+    pub path: PathBuf,
+    pub singer: Option<String>,
+}
+
+/// Songs played one after the other
+#[derive(Debug, Clone)]
+pub struct Queue {
+    items: Vec<QueueItem>,
+    current: usize,
+}
+
+impl Queue {
+    pub fn new(items: Vec<QueueItem>) -> Queue {
+        Queue {
+            items: items,
+            current: 0,
+        }
+    }
+
+    /// Reads a queue file : one video per line, optionally followed by a tab
+    /// and the name of the singer. Empty lines and lines beginning with '#'
+    /// are ignored, relative paths are relative to the queue file.
+    pub fn load(path: &Path) -> Result<Vec<QueueItem>, String> {
+        let content = try!(lyrics::read_file(path).map_err(|e| e.to_string()));
+        let directory = path.parent().unwrap_or(Path::new("."));
+        Ok(content.lines()
+                  .map(|line| line.trim_right_matches('\r'))
+                  .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+                  .map(|line| {
+                      let mut fields = line.splitn(2, '\t');
+                      let video = fields.next().unwrap().trim();
+                      let singer = fields.next()
+                                         .map(|singer| singer.trim())
+                                         .and_then(|singer| if singer.is_empty() {
+                                             None
+                                         } else {
+                                             Some(singer.to_string())
+                                         });
+                      QueueItem {
+                          path: directory.join(video),
+                          singer: singer,
+                      }
+                  })
+                  .collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn current(&self) -> Option<&QueueItem> {
+        self.items.get(self.current)
+    }
+
+    /// Songs played after the current one
+    pub fn upcoming(&self) -> &[QueueItem] {
+        &self.items[cmp::min(self.current + 1, self.items.len())..]
+    }
+
+    /// Moves to the next song, returns false at the end of the queue
+    pub fn advance(&mut self) -> bool {
+        if self.current + 1 < self.items.len() {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
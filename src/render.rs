@@ -0,0 +1,140 @@
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+
+use sdl2::surface::SurfaceRef;
+
+/// ffmpeg name of the ARGB8888 pixel format of SDL surfaces
+#[cfg(target_endian = "little")]
+const PIXEL_FORMAT: &'static str = "bgra";
+#[cfg(target_endian = "big")]
+const PIXEL_FORMAT: &'static str = "argb";
+
+fn ffmpeg() -> Command {
+    let mut command = Command::new("ffmpeg");
+    command.args(&["-loglevel", "error", "-nostdin"]);
+    command
+}
+
+fn size_argument((width, height): (u32, u32)) -> String {
+    format!("{}x{}", width, height)
+}
+
+/// ffmpeg decoding the frames of a video, scaled to fit `size` with black
+/// bars around them
+pub struct Decoder {
+    child: Child,
+    stdout: ChildStdout,
+    size: (u32, u32),
+    fps: f64,
+}
+
+impl Decoder {
+    /// Frames are output at a constant `fps`, duplicated or dropped as
+    /// needed, so that the time of a frame follows from its number
+    pub fn spawn(video_path: &Path, size: (u32, u32), fps: f64) -> io::Result<Decoder> {
+        let (width, height) = size;
+        let filter = format!("fps={fps},\
+                              scale={w}:{h}:force_original_aspect_ratio=decrease,\
+                              pad={w}:{h}:(ow-iw)/2:(oh-ih)/2",
+                             fps = fps,
+                             w = width,
+                             h = height);
+        let mut child = try!(ffmpeg().arg("-i")
+                                     .arg(video_path)
+                                     .args(&["-map", "0:v:0", "-vf", &filter])
+                                     .args(&["-f", "rawvideo", "-pix_fmt", PIXEL_FORMAT, "-"])
+                                     .stdout(Stdio::piped())
+                                     .spawn());
+        let stdout = child.stdout.take().unwrap();
+        Ok(Decoder {
+            child: child,
+            stdout: stdout,
+            size: size,
+            fps: fps,
+        })
+    }
+
+    /// Frame rate of the decoded frames
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    /// Reads the next frame into `surface`, returns false once the video is
+    /// over
+    pub fn read_frame(&mut self, surface: &mut SurfaceRef) -> io::Result<bool> {
+        let (width, height) = self.size;
+        let mut frame = vec![0u8; width as usize * height as usize * 4];
+        match self.stdout.read_exact(&mut frame) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e),
+        }
+        let row_length = width as usize * 4;
+        let pitch = surface.pitch() as usize;
+        surface.with_lock_mut(|pixels: &mut [u8]| {
+            for (row, line) in frame.chunks(row_length).enumerate() {
+                pixels[row * pitch..row * pitch + row_length].copy_from_slice(line);
+            }
+        });
+        Ok(true)
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        // the rest of the video is not needed
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// ffmpeg encoding frames into a video, with the audio of the original file
+pub struct Encoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+}
+
+impl Encoder {
+    pub fn spawn(output_path: &Path,
+                 audio_path: &Path,
+                 size: (u32, u32),
+                 fps: f64)
+                 -> io::Result<Encoder> {
+        let mut child = try!(ffmpeg().args(&["-y", "-f", "rawvideo", "-pix_fmt", PIXEL_FORMAT])
+                                     .args(&["-s", &size_argument(size)])
+                                     .args(&["-r", &fps.to_string(), "-i", "-"])
+                                     .arg("-i")
+                                     .arg(audio_path)
+                                     .args(&["-map", "0:v", "-map", "1:a:0?"])
+                                     .args(&["-pix_fmt", "yuv420p", "-shortest"])
+                                     .arg(output_path)
+                                     .stdin(Stdio::piped())
+                                     .spawn());
+        let stdin = child.stdin.take();
+        Ok(Encoder {
+            child: child,
+            stdin: stdin,
+        })
+    }
+
+    pub fn write_frame(&mut self, surface: &SurfaceRef) -> io::Result<()> {
+        let row_length = surface.width() as usize * 4;
+        let pitch = surface.pitch() as usize;
+        let height = surface.height() as usize;
+        let stdin = self.stdin.as_mut().unwrap();
+        surface.with_lock(|pixels: &[u8]| {
+            for row in 0..height {
+                try!(stdin.write_all(&pixels[row * pitch..row * pitch + row_length]));
+            }
+            Ok(())
+        })
+    }
+
+    /// Closes the input of ffmpeg and waits for it to write the video
+    pub fn finish(mut self) -> io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        self.child.wait()
+    }
+}
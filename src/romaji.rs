@@ -0,0 +1,342 @@
+use lyrics::{Line, Song};
+
+/// How romaji are displayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RomajiMode {
+    Off,
+    Only,
+    Alongside,
+}
+
+impl RomajiMode {
+    pub fn next(self) -> RomajiMode {
+        match self {
+            RomajiMode::Off => RomajiMode::Only,
+            RomajiMode::Only => RomajiMode::Alongside,
+            RomajiMode::Alongside => RomajiMode::Off,
+        }
+    }
+}
+
+/// Returns a copy of the song where hiragana and katakana are transliterated
+/// into Hepburn romaji. Syllables and their timings are kept as is, other
+/// characters are left untouched.
+pub fn romanize_song(song: &Song) -> Song {
+    let mut romanized = song.clone();
+    for line in &mut romanized.lines {
+        romanize_line(line);
+    }
+    romanized
+}
+
+fn romanize_line(line: &mut Line) {
+    let texts = {
+        let syllables: Vec<&str> = line.syllables.iter().map(|s| &*s.text).collect();
+        romanize_syllables(&syllables)
+    };
+    for (syllable, text) in line.syllables.iter_mut().zip(texts.into_iter()) {
+        syllable.text = text;
+    }
+}
+
+/// Transliterates the syllables of a line. The line is read as a whole so
+/// that a small tsu or a yōon spanning two syllables is handled, the romaji
+/// are then given to the syllable the kana came from.
+pub fn romanize_syllables(syllables: &[&str]) -> Vec<String> {
+    let chars: Vec<(char, usize)> = syllables.iter()
+                                             .enumerate()
+                                             .flat_map(|(index, text)| {
+                                                 text.chars().map(move |c| (to_hiragana(c), index))
+                                             })
+                                             .collect();
+    let mut result = vec![String::new(); syllables.len()];
+    // syllable of a small tsu waiting for the consonant it doubles
+    let mut small_tsu: Option<usize> = None;
+    let mut index = 0;
+    while index < chars.len() {
+        let (c, syllable) = chars[index];
+        let next = chars.get(index + 1).map(|&(c, _)| c);
+        index += 1;
+        if c == 'っ' {
+            small_tsu = Some(syllable);
+            continue;
+        }
+        if c == 'ー' {
+            lengthen_vowel(&mut result, syllable);
+            continue;
+        }
+        let mut romaji = match next.and_then(|next| digraph(c, next)) {
+            Some(romaji) => {
+                index += 1;
+                romaji
+            }
+            None => {
+                match kana(c) {
+                    Some(romaji) => romaji.to_string(),
+                    None => {
+                        result[syllable].push(punctuation(c));
+                        small_tsu = None;
+                        continue;
+                    }
+                }
+            }
+        };
+        if let Some(tsu_syllable) = small_tsu.take() {
+            if romaji.starts_with("ch") {
+                result[tsu_syllable].push('t');
+            } else if let Some(consonant) = romaji.chars().next() {
+                if !is_vowel(consonant) {
+                    result[tsu_syllable].push(consonant);
+                }
+            }
+        }
+        if c == 'ん' {
+            let before_vowel = chars.get(index)
+                                    .and_then(|&(c, _)| kana(c))
+                                    .map(|next| next.starts_with(|c: char| is_vowel(c) || c == 'y'))
+                                    .unwrap_or(false);
+            if before_vowel {
+                romaji.push('\'');
+            }
+        }
+        // おう, おお and うう sung in a single syllable are long vowels
+        if let Some(&(following, following_syllable)) = chars.get(index) {
+            let long = match (romaji.chars().last(), following) {
+                (Some('o'), 'う') | (Some('o'), 'お') | (Some('u'), 'う') => true,
+                _ => false,
+            };
+            if long && following_syllable == syllable {
+                let vowel = romaji.pop().unwrap();
+                romaji.push(macron(vowel));
+                index += 1;
+            }
+        }
+        result[syllable].push_str(&romaji);
+    }
+    result
+}
+
+/// Handles the katakana long vowel mark, within a syllable the previous vowel
+/// gets a macron, otherwise it is repeated so the syllable isn't empty
+fn lengthen_vowel(result: &mut Vec<String>, syllable: usize) {
+    let previous_vowel = result[..syllable + 1]
+                             .iter()
+                             .rev()
+                             .filter_map(|text| text.chars().last())
+                             .next()
+                             .and_then(|c| if is_vowel(c) { Some(c) } else { None });
+    match previous_vowel {
+        Some(vowel) if !result[syllable].is_empty() => {
+            result[syllable].pop();
+            result[syllable].push(macron(vowel));
+        }
+        Some(vowel) => result[syllable].push(vowel),
+        None => result[syllable].push('-'),
+    }
+}
+
+fn is_vowel(c: char) -> bool {
+    match c {
+        'a' | 'i' | 'u' | 'e' | 'o' => true,
+        _ => false,
+    }
+}
+
+fn macron(vowel: char) -> char {
+    match vowel {
+        'a' => 'ā',
+        'i' => 'ī',
+        'u' => 'ū',
+        'e' => 'ē',
+        'o' => 'ō',
+        other => other,
+    }
+}
+
+fn to_hiragana(c: char) -> char {
+    let code = c as u32;
+    if code >= 0x30A1 && code <= 0x30F6 {
+        ::std::char::from_u32(code - 0x60).unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+fn punctuation(c: char) -> char {
+    match c {
+        '　' => ' ',
+        '、' => ',',
+        '。' => '.',
+        '！' => '!',
+        '？' => '?',
+        '「' | '」' | '『' | '』' => '"',
+        other => other,
+    }
+}
+
+/// Yōon and the combinations used to write foreign sounds
+fn digraph(first: char, second: char) -> Option<String> {
+    let base = match kana(first) {
+        Some(base) => base,
+        None => return None,
+    };
+    let special = match (first, second) {
+        ('ふ', 'ぁ') => Some("fa"),
+        ('ふ', 'ぃ') => Some("fi"),
+        ('ふ', 'ぇ') => Some("fe"),
+        ('ふ', 'ぉ') => Some("fo"),
+        ('ゔ', 'ぁ') => Some("va"),
+        ('ゔ', 'ぃ') => Some("vi"),
+        ('ゔ', 'ぇ') => Some("ve"),
+        ('ゔ', 'ぉ') => Some("vo"),
+        ('て', 'ぃ') => Some("ti"),
+        ('で', 'ぃ') => Some("di"),
+        ('と', 'ぅ') => Some("tu"),
+        ('ど', 'ぅ') => Some("du"),
+        ('う', 'ぃ') => Some("wi"),
+        ('う', 'ぇ') => Some("we"),
+        ('う', 'ぉ') => Some("wo"),
+        ('し', 'ぇ') => Some("she"),
+        ('ち', 'ぇ') => Some("che"),
+        ('じ', 'ぇ') => Some("je"),
+        _ => None,
+    };
+    if let Some(romaji) = special {
+        return Some(romaji.to_string());
+    }
+    let vowel = match second {
+        'ゃ' => "a",
+        'ゅ' => "u",
+        'ょ' => "o",
+        _ => return None,
+    };
+    if !base.ends_with('i') || base.len() < 2 {
+        return None;
+    }
+    let consonant = &base[..base.len() - 1];
+    // し, ち and じ are already palatalized : sha, cha, ja
+    if consonant == "sh" || consonant == "ch" || consonant == "j" {
+        Some(format!("{}{}", consonant, vowel))
+    } else {
+        Some(format!("{}y{}", consonant, vowel))
+    }
+}
+
+fn kana(c: char) -> Option<&'static str> {
+    let romaji = match c {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' => "e",
+        'お' | 'ぉ' => "o",
+        'か' | 'ゕ' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' | 'ゖ' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' => "ji",
+        'ず' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'ぢ' => "ji",
+        'づ' => "zu",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' | 'ゃ' => "ya",
+        'ゆ' | 'ゅ' => "yu",
+        'よ' | 'ょ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ゐ' => "i",
+        'ゑ' => "e",
+        'を' => "o",
+        'ん' => "n",
+        'ゔ' => "vu",
+        _ => return None,
+    };
+    Some(romaji)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::romanize_syllables;
+
+    #[test]
+    fn small_tsu() {
+        assert_eq!(romanize_syllables(&["き", "っ", "て"]), vec!["ki", "t", "te"]);
+        assert_eq!(romanize_syllables(&["きって"]), vec!["kitte"]);
+        assert_eq!(romanize_syllables(&["ま", "っち"]), vec!["ma", "tchi"]);
+    }
+
+    #[test]
+    fn yoon() {
+        assert_eq!(romanize_syllables(&["きょう"]), vec!["kyō"]);
+        assert_eq!(romanize_syllables(&["しゃ", "ちゅ", "じょ"]), vec!["sha", "chu", "jo"]);
+        // split across two syllables, the vowel is sung twice
+        assert_eq!(romanize_syllables(&["きょ", "う"]), vec!["kyo", "u"]);
+    }
+
+    #[test]
+    fn syllabic_n() {
+        assert_eq!(romanize_syllables(&["こ", "ん", "や"]), vec!["ko", "n'", "ya"]);
+        assert_eq!(romanize_syllables(&["さん", "ぽ"]), vec!["san", "po"]);
+    }
+
+    #[test]
+    fn katakana_long_vowel() {
+        assert_eq!(romanize_syllables(&["ラー", "メ", "ン"]), vec!["rā", "me", "n"]);
+        assert_eq!(romanize_syllables(&["ラ", "ー", "メン"]), vec!["ra", "a", "men"]);
+    }
+
+    #[test]
+    fn punctuation_and_other_characters() {
+        assert_eq!(romanize_syllables(&["あ、", "い。", "Oh!"]), vec!["a,", "i.", "Oh!"]);
+        assert_eq!(romanize_syllables(&["「う」", "　", "え？"]), vec!["\"u\"", " ", "e?"]);
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use displayer::ARGB_BYTES;
+
+/// A frame read back from the renderer, in ARGB8888 rows without padding
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Screenshot {
+    /// Writes the frame as an RGB PNG. The image data is stored without
+    /// compression, which keeps the encoder small and any viewer reads it.
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        let mut data = Vec::with_capacity((self.width as usize * 3 + 1) * self.height as usize);
+        for row in self.pixels.chunks(self.width as usize * 4) {
+            // no filter
+            data.push(0);
+            for pixel in row.chunks(4) {
+                data.extend(ARGB_BYTES[1..].iter().map(|&byte| pixel[byte]));
+            }
+        }
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&be_bytes(self.width));
+        header.extend_from_slice(&be_bytes(self.height));
+        // 8 bits per channel, RGB, default compression, filter and interlace
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut file = BufWriter::new(try!(File::create(path)));
+        try!(file.write_all(b"\x89PNG\r\n\x1a\n"));
+        try!(write_chunk(&mut file, b"IHDR", &header));
+        try!(write_chunk(&mut file, b"IDAT", &zlib_stored(&data)));
+        try!(write_chunk(&mut file, b"IEND", &[]));
+        file.flush()
+    }
+}
+
+fn be_bytes(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8], data: &[u8]) -> io::Result<()> {
+    try!(writer.write_all(&be_bytes(data.len() as u32)));
+    try!(writer.write_all(kind));
+    try!(writer.write_all(data));
+    let crc = crc32(kind.iter().chain(data.iter()));
+    writer.write_all(&be_bytes(crc))
+}
+
+fn crc32<'a, I: Iterator<Item = &'a u8>>(bytes: I) -> u32 {
+    let mut table = [0u32; 256];
+    for (index, entry) in table.iter_mut().enumerate() {
+        let mut value = index as u32;
+        for _ in 0..8 {
+            value = if value & 1 == 1 {
+                0xedb88320 ^ (value >> 1)
+            } else {
+                value >> 1
+            };
+        }
+        *entry = value;
+    }
+    !bytes.fold(!0, |crc, &byte| table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+/// zlib stream of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend_from_slice(&[length as u8, (length >> 8) as u8]);
+        stream.extend_from_slice(&[!length as u8, (!length >> 8) as u8]);
+        stream.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    stream.extend_from_slice(&be_bytes((b << 16) | a));
+    stream
+}
+
+/// Path of a new screenshot of `video_path` in `directory`, named after the
+/// video and the current UTC time, as in `song-20160412-213005.123.png`
+pub fn file_path(directory: &Path, video_path: &Path) -> PathBuf {
+    let stem = video_path.file_stem()
+                         .map(|stem| stem.to_string_lossy().into_owned())
+                         .unwrap_or_else(|| "screenshot".to_string());
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = now.as_secs();
+    let (year, month, day) = civil_date((seconds / 86400) as i64);
+    let time = seconds % 86400;
+    directory.join(format!("{}-{:04}{:02}{:02}-{:02}{:02}{:02}.{:03}.png",
+                           stem,
+                           year,
+                           month,
+                           day,
+                           time / 3600,
+                           time / 60 % 60,
+                           time % 60,
+                           now.subsec_nanos() / 1_000_000))
+}
+
+/// Year, month and day of a number of days since 1970-01-01
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 -
+                       day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // months starting from March, leap days come last
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{civil_date, crc32, zlib_stored};
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"IEND".iter()), 0xAE426082);
+        assert_eq!(crc32([].iter()), 0);
+    }
+
+    #[test]
+    fn zlib_single_block() {
+        let stream = zlib_stored(b"Wikipedia");
+        assert_eq!(&stream[..7], &[0x78, 0x01, 1, 9, 0, 0xf6, 0xff]);
+        assert_eq!(&stream[7..16], b"Wikipedia");
+        // Adler-32 of the data, big endian
+        assert_eq!(&stream[16..], &[0x11, 0xe6, 0x03, 0x98]);
+    }
+
+    #[test]
+    fn zlib_empty() {
+        assert_eq!(zlib_stored(&[]), vec![0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn zlib_blocks_split() {
+        let data = vec![0u8; 65536];
+        let stream = zlib_stored(&data);
+        assert_eq!(stream.len(), 2 + 5 + 65535 + 5 + 1 + 4);
+        // a full block which isn't the last one, then a single byte one
+        assert_eq!(&stream[2..7], &[0, 0xff, 0xff, 0, 0]);
+        assert_eq!(&stream[7 + 65535..7 + 65535 + 5], &[1, 1, 0, 0xfe, 0xff]);
+        assert_eq!(&stream[stream.len() - 4..], &[0x00, 0x0f, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(-1), (1969, 12, 31));
+        assert_eq!(civil_date(11016), (2000, 2, 29));
+        assert_eq!(civil_date(10956), (1999, 12, 31));
+        assert_eq!(civil_date(10957), (2000, 1, 1));
+    }
+}
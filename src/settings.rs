@@ -0,0 +1,42 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::json;
+
+/// Per-song settings tweaked while playing, stored next to the video file
+#[derive(Debug, Clone, Default, RustcEncodable, RustcDecodable)]
+pub struct SongSettings {
+    /// Delay applied to the lyrics in milliseconds, negative values make
+    /// them appear earlier
+    pub offset: i32,
+}
+
+impl SongSettings {
+    pub fn path(video_path: &Path) -> PathBuf {
+        video_path.with_extension("settings.json")
+    }
+
+    /// Loads the settings of a song, or the default ones if there are none
+    pub fn load(video_path: &Path) -> SongSettings {
+        let path = SongSettings::path(video_path);
+        let mut content = String::new();
+        match File::open(&path).and_then(|mut file| file.read_to_string(&mut content)) {
+            Ok(_) => {
+                json::decode(&content).unwrap_or_else(|e| {
+                    warn!("Ignoring invalid settings file {} : {}", path.display(), e);
+                    SongSettings::default()
+                })
+            }
+            Err(_) => SongSettings::default(),
+        }
+    }
+
+    pub fn save(&self, video_path: &Path) -> io::Result<()> {
+        let path = SongSettings::path(video_path);
+        let content = json::encode(self).unwrap();
+        let mut file = try!(File::create(&path));
+        file.write_all(content.as_bytes())
+    }
+}
@@ -0,0 +1,141 @@
+use rustc_serialize::json::Json;
+
+use lyrics;
+use lyrics::Rgb;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Shadow {
+    pub color: Rgb,
+    /// Opacity of the shadow, from 0 to 255
+    pub alpha: u8,
+    /// Offset of the shadow from the text, in pixels
+    pub offset: (i32, i32),
+    /// Blur radius, in pixels
+    pub blur: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Glow {
+    pub color: Rgb,
+    /// How far the glow spreads around the outline, in pixels
+    pub radius: u32,
+}
+
+/// How text is drawn. From the back to the front, the layers are the glow,
+/// the shadow, the outline and the fill.
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    /// Fill of text which doesn't get the colours of a singer
+    pub fill: Rgb,
+    /// Brightness of the bottom of the fill relative to its top, 1 for a
+    /// plain fill
+    pub gradient: f32,
+    pub outline_color: Rgb,
+    /// In pixels, 0 to draw no outline
+    pub outline_width: u16,
+    pub shadow: Option<Shadow>,
+    pub glow: Option<Glow>,
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            fill: (180, 180, 180),
+            gradient: 1.0,
+            outline_color: (0, 0, 0),
+            outline_width: 2,
+            shadow: None,
+            glow: None,
+        }
+    }
+}
+
+fn find_color(json: &Json, key: &str) -> Option<Rgb> {
+    json.find(key).and_then(|j| j.as_string()).and_then(lyrics::parse_color)
+}
+
+impl TextStyle {
+    /// Reads a style from a JSON object, missing keys keep their value. The
+    /// shadow and the glow are enabled by their own object, or disabled by
+    /// `false`.
+    pub fn apply(&mut self, json: &Json) {
+        if let Some(fill) = find_color(json, "fill") {
+            self.fill = fill;
+        }
+        if let Some(gradient) = json.find("gradient").and_then(|j| j.as_f64()) {
+            self.gradient = gradient as f32;
+        }
+        if let Some(outline_color) = find_color(json, "outline_color") {
+            self.outline_color = outline_color;
+        }
+        if let Some(outline_width) = json.find("outline_width").and_then(|j| j.as_u64()) {
+            self.outline_width = outline_width as u16;
+        }
+        match json.find("shadow") {
+            Some(&Json::Boolean(false)) => self.shadow = None,
+            Some(shadow_json) if shadow_json.is_object() => {
+                let mut shadow = self.shadow.unwrap_or(Shadow {
+                    color: (0, 0, 0),
+                    alpha: 160,
+                    offset: (3, 3),
+                    blur: 2,
+                });
+                if let Some(color) = find_color(shadow_json, "color") {
+                    shadow.color = color;
+                }
+                if let Some(alpha) = shadow_json.find("alpha").and_then(|j| j.as_u64()) {
+                    shadow.alpha = ::std::cmp::min(alpha, 255) as u8;
+                }
+                if let Some(x) = shadow_json.find("x").and_then(|j| j.as_i64()) {
+                    shadow.offset.0 = x as i32;
+                }
+                if let Some(y) = shadow_json.find("y").and_then(|j| j.as_i64()) {
+                    shadow.offset.1 = y as i32;
+                }
+                if let Some(blur) = shadow_json.find("blur").and_then(|j| j.as_u64()) {
+                    shadow.blur = blur as u32;
+                }
+                self.shadow = Some(shadow);
+            }
+            _ => {}
+        }
+        match json.find("glow") {
+            Some(&Json::Boolean(false)) => self.glow = None,
+            Some(glow_json) if glow_json.is_object() => {
+                let mut glow = self.glow.unwrap_or(Glow {
+                    color: (255, 255, 255),
+                    radius: 4,
+                });
+                if let Some(color) = find_color(glow_json, "color") {
+                    glow.color = color;
+                }
+                if let Some(radius) = glow_json.find("radius").and_then(|j| j.as_u64()) {
+                    glow.radius = radius as u32;
+                }
+                self.glow = Some(glow);
+            }
+            _ => {}
+        }
+    }
+
+    /// Outline widths the fonts must be loaded with to draw this style
+    pub fn outline_widths(&self) -> Vec<u16> {
+        let mut widths = vec![self.outline_width];
+        if let Some(glow) = self.glow {
+            widths.push(self.outline_width + glow.radius as u16);
+        }
+        widths
+    }
+
+    /// Space the glow and the shadow need around the outline, in pixels
+    pub fn padding(&self) -> u32 {
+        let glow = self.glow.map(|glow| 2 * glow.radius).unwrap_or(0);
+        let shadow = self.shadow
+                         .map(|shadow| {
+                             ::std::cmp::max(shadow.offset.0.abs(), shadow.offset.1.abs()) as u32 +
+                             shadow.blur
+                         })
+                         .unwrap_or(0);
+        ::std::cmp::max(glow, shadow)
+    }
+}
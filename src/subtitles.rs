@@ -0,0 +1,325 @@
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use lyrics;
+use lyrics::{Line, Metadata, Song, Source, Syllable};
+use mpv::Mpv;
+use tracks;
+use wakeup;
+
+/// Looks for a text subtitle track inside the video and converts it to a
+/// song, ASS tracks are preferred since they may hold karaoke timings
+pub fn load_embedded(mpv: &Mpv, video_path: &Path) -> Option<Song> {
+    find_track(mpv).and_then(|track| extract_song(video_path, &track))
+}
+
+/// Subtitle track of the video lyrics are read from, if any
+pub fn find_track(mpv: &Mpv) -> Option<tracks::Track> {
+    let subtitle_tracks: Vec<tracks::Track> = tracks::track_list(mpv)
+                                                  .into_iter()
+                                                  .filter(|track| {
+                                                      track.kind == "sub" && !track.external &&
+                                                      track.ff_index.is_some()
+                                                  })
+                                                  .collect();
+    let codec_is = |track: &tracks::Track, codecs: &[&str]| {
+        track.codec.as_ref().map(|codec| codecs.contains(&&**codec)).unwrap_or(false)
+    };
+    let track = subtitle_tracks.iter()
+                               .find(|track| codec_is(track, &["ass", "ssa"]))
+                               .or_else(|| {
+                                   subtitle_tracks.iter().find(|track| {
+                                       codec_is(track, &["subrip", "srt", "webvtt", "mov_text", "text"])
+                                   })
+                               });
+    track.cloned()
+}
+
+fn extract_song(video_path: &Path, track: &tracks::Track) -> Option<Song> {
+    info!("Using embedded subtitle track {} ({})",
+          track.id,
+          track.title.as_ref().map(|t| &**t).unwrap_or("untitled"));
+    match extract_track(video_path, track.ff_index.unwrap()) {
+        Ok(ass) => Some(parse_ass(&ass)),
+        Err(e) => {
+            warn!("Failed to extract subtitle track {} : {}", track.id, e);
+            None
+        }
+    }
+}
+
+/// Lyrics being extracted from a subtitle track by another thread, ffmpeg
+/// reading the whole video
+pub struct Extraction {
+    receiver: Receiver<Option<Song>>,
+}
+
+impl Extraction {
+    /// Starts extracting `track`. The SDL user event `wakeup_event`, as
+    /// returned by `wakeup::register`, is pushed once it is done.
+    pub fn start(video_path: &Path, track: tracks::Track, wakeup_event: *mut c_void) -> Extraction {
+        let (sender, receiver) = channel();
+        let video_path: PathBuf = video_path.to_path_buf();
+        // raw pointers can't be sent to another thread
+        let wakeup_event = wakeup_event as usize;
+        thread::spawn(move || {
+            // the receiver is gone if the song ended meanwhile
+            let _ = sender.send(extract_song(&video_path, &track));
+            unsafe { wakeup::wake_up(wakeup_event as *mut c_void) };
+        });
+        Extraction { receiver: receiver }
+    }
+
+    /// The extracted song once the extraction is over, None as long as it
+    /// isn't. Some(None) if it failed.
+    pub fn result(&self) -> Option<Option<Song>> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Extracts a subtitle stream with ffmpeg, converted to ASS whatever its
+/// original format
+pub fn extract_track(video_path: &Path, ff_index: i64) -> Result<String, String> {
+    let output = try!(Command::new("ffmpeg")
+                          .arg("-v")
+                          .arg("error")
+                          .arg("-i")
+                          .arg(video_path)
+                          .arg("-map")
+                          .arg(format!("0:{}", ff_index))
+                          .arg("-f")
+                          .arg("ass")
+                          .arg("-")
+                          .output()
+                          .map_err(|e| format!("cannot run ffmpeg : {}", e)));
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Converts the dialogue lines of an ASS script to a song. Karaoke tags
+/// (`\k`, `\K`, `\kf` and `\ko`) split lines into syllables, lines without
+/// them become a single syllable. Every style is considered a singer.
+pub fn parse_ass(ass: &str) -> Song {
+    let mut song = Song {
+        lines: Vec::new(),
+        singers: Vec::new(),
+        metadata: Metadata::default(),
+    };
+    let mut in_events = false;
+    let mut format: Vec<String> = ["Layer", "Start", "End", "Style", "Name", "MarginL",
+                                   "MarginR", "MarginV", "Effect", "Text"]
+                                      .iter()
+                                      .map(|field| field.to_string())
+                                      .collect();
+    for (index, line) in ass.lines().enumerate() {
+        let line = line.trim_right_matches('\r');
+        if line.starts_with('[') {
+            in_events = line.trim().to_lowercase() == "[events]";
+            continue;
+        }
+        if line.starts_with("Title:") {
+            let title = line["Title:".len()..].trim();
+            if !title.is_empty() && title != "<untitled>" {
+                song.metadata.title = Some(title.to_string());
+            }
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        if line.starts_with("Format:") {
+            format = line["Format:".len()..].split(',').map(|f| f.trim().to_string()).collect();
+            continue;
+        }
+        if !line.starts_with("Dialogue:") {
+            continue;
+        }
+        // the text is the last field and may contain commas
+        let fields: Vec<&str> = line["Dialogue:".len()..].splitn(format.len(), ',').collect();
+        let field = |name: &str| {
+            format.iter().position(|f| f == name).and_then(|i| fields.get(i)).map(|f| *f)
+        };
+        let (start, end, text) = match (field("Start").and_then(parse_time),
+                                        field("End").and_then(parse_time),
+                                        field("Text")) {
+            (Some(start), Some(end), Some(text)) => (start, end, text),
+            _ => continue,
+        };
+        let singers = match field("Style") {
+            Some(style) => vec![lyrics::singer_index(&mut song.singers, style.trim())],
+            None => Vec::new(),
+        };
+        let syllables = parse_karaoke(text, start, end, &singers, index + 1);
+        if !syllables.is_empty() {
+            song.lines.push(Line { syllables: syllables });
+        }
+    }
+    song.lines.sort_by_key(|line| line.begin());
+    song
+}
+
+/// Parses an ASS timestamp, H:MM:SS.cc, into milliseconds
+fn parse_time(time: &str) -> Option<u32> {
+    let parts: Vec<&str> = time.trim().split(|c| c == ':' || c == '.').collect();
+    if parts.len() != 4 {
+        return None;
+    }
+    let numbers: Vec<u32> = parts.iter().filter_map(|part| part.parse().ok()).collect();
+    if numbers.len() != 4 {
+        return None;
+    }
+    let fraction = match parts[3].len() {
+        1 => numbers[3] * 100,
+        2 => numbers[3] * 10,
+        _ => numbers[3],
+    };
+    Some(((numbers[0] * 60 + numbers[1]) * 60 + numbers[2]) * 1000 + fraction)
+}
+
+/// Returns the duration in milliseconds of a karaoke override tag
+fn karaoke_duration(tag: &str) -> Option<u32> {
+    let duration = if tag.starts_with("kf") || tag.starts_with("ko") {
+        &tag[2..]
+    } else if tag.starts_with('k') || tag.starts_with('K') {
+        &tag[1..]
+    } else {
+        return None;
+    };
+    duration.trim().parse::<u32>().ok().map(|centiseconds| centiseconds * 10)
+}
+
+fn parse_karaoke(text: &str,
+                 start: u32,
+                 end: u32,
+                 singers: &[usize],
+                 lyr_line: usize)
+                 -> Vec<Syllable> {
+    // text of each syllable, with its begin and end once a karaoke tag is met
+    let mut pieces: Vec<(String, Option<(u32, u32)>)> = vec![(String::new(), None)];
+    let mut next_begin = start;
+    let mut rest = text;
+    while !rest.is_empty() {
+        // an unclosed brace is no override block, it is kept as text
+        let close = if rest.starts_with('{') {
+            rest.find('}')
+        } else {
+            None
+        };
+        if let Some(close) = close {
+            for tag in rest[1..close].split('\\') {
+                if let Some(duration) = karaoke_duration(tag) {
+                    pieces.push((String::new(), Some((next_begin, next_begin + duration))));
+                    next_begin += duration;
+                }
+            }
+            rest = &rest[close + 1..];
+        } else if rest.starts_with("\\N") || rest.starts_with("\\n") || rest.starts_with("\\h") {
+            pieces.last_mut().unwrap().0.push(' ');
+            rest = &rest[2..];
+        } else {
+            let c = rest.chars().next().unwrap();
+            pieces.last_mut().unwrap().0.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    let has_karaoke = pieces.iter().any(|&(_, timing)| timing.is_some());
+    pieces.into_iter()
+          .filter(|&(ref text, _)| !text.is_empty())
+          .map(|(text, timing)| {
+              let (begin, end) = match timing {
+                  Some(timing) => timing,
+                  None if has_karaoke => (start, start),
+                  None => (start, end),
+              };
+              Syllable {
+                  text: text,
+                  begin: begin,
+                  end: end,
+                  singers: singers.to_vec(),
+                  source: Source {
+                      lyr_line: lyr_line,
+                      lyr_column: 1,
+                      frm_line: 0,
+                  },
+              }
+          })
+          .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_ass, parse_karaoke};
+
+    fn syllables(text: &str) -> Vec<(String, u32, u32)> {
+        parse_karaoke(text, 1000, 5000, &[], 1)
+            .into_iter()
+            .map(|syllable| (syllable.text, syllable.begin, syllable.end))
+            .collect()
+    }
+
+    fn expected(syllables: &[(&str, u32, u32)]) -> Vec<(String, u32, u32)> {
+        syllables.iter().map(|&(text, begin, end)| (text.to_string(), begin, end)).collect()
+    }
+
+    #[test]
+    fn karaoke_tags() {
+        assert_eq!(syllables("{\\k50}ka{\\kf30}ra{\\K20}o{\\ko10}ke"),
+                   expected(&[("ka", 1000, 1500),
+                              ("ra", 1500, 1800),
+                              ("o", 1800, 2000),
+                              ("ke", 2000, 2100)]));
+    }
+
+    #[test]
+    fn other_tags_are_ignored() {
+        assert_eq!(syllables("{\\be1\\k50\\c&HFFFFFF&}ka{\\i1}na"),
+                   expected(&[("kana", 1000, 1500)]));
+    }
+
+    #[test]
+    fn no_karaoke() {
+        assert_eq!(syllables("Hello"), expected(&[("Hello", 1000, 5000)]));
+    }
+
+    #[test]
+    fn unclosed_brace() {
+        assert_eq!(syllables("{\\k50}ka{"), expected(&[("ka{", 1000, 1500)]));
+        assert_eq!(syllables("{"), expected(&[("{", 1000, 5000)]));
+        assert_eq!(syllables("a{é"), expected(&[("a{é", 1000, 5000)]));
+    }
+
+    #[test]
+    fn line_breaks() {
+        assert_eq!(syllables("one\\Ntwo\\nthree\\hfour"),
+                   expected(&[("one two three four", 1000, 5000)]));
+    }
+
+    #[test]
+    fn ass_script() {
+        let song = parse_ass("[Script Info]\r\n\
+                              Title: Song\r\n\
+                              \r\n\
+                              [Events]\r\n\
+                              Format: Layer, Start, End, Style, Name, MarginL, MarginR, \
+                              MarginV, Effect, Text\r\n\
+                              Dialogue: 0,0:00:03.00,0:00:05.00,Duo,,0,0,0,,Second, line\r\n\
+                              Dialogue: 0,0:00:01.50,0:00:02.00,Lead,,0,0,0,,{\\k20}fi{\\k30}rst\r\n\
+                              Comment: 0,0:00:01.00,0:00:02.00,Lead,,0,0,0,,ignored\r\n");
+        assert_eq!(song.metadata.title, Some("Song".to_string()));
+        assert_eq!(song.singers.len(), 2);
+        assert_eq!(song.lines.len(), 2);
+        let first: Vec<(&str, u32, u32)> = song.lines[0]
+                                               .syllables
+                                               .iter()
+                                               .map(|s| (&*s.text, s.begin, s.end))
+                                               .collect();
+        assert_eq!(first, vec![("fi", 1500, 1700), ("rst", 1700, 2000)]);
+        assert_eq!(song.lines[1].syllables[0].text, "Second, line");
+        assert_eq!((song.lines[1].begin(), song.lines[1].end()), (3000, 5000));
+    }
+}
@@ -0,0 +1,267 @@
+use rustc_serialize::json::Json;
+
+use lyrics;
+use lyrics::{Metadata, Rgb, Song, Syllable};
+
+/// Colours of the lyrics and of the player messages
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    /// Colours of syllables without singer, or of every syllable when the
+    /// colours of the singers are overridden
+    pub sung: Rgb,
+    pub unsung: Rgb,
+    /// Colour of the syllable being sung, the sung colour if None
+    pub active: Option<Rgb>,
+    /// Replaces the outline colour of the text style
+    pub outline: Option<Rgb>,
+    /// Opacity of a black layer dimming the video behind the lyrics, 0 to
+    /// leave it untouched
+    pub background_dim: u8,
+    /// Colour of the OSD and the other player messages, the fill of the text
+    /// style if None
+    pub osd: Option<Rgb>,
+    /// Ignores the colours singers get in lyr files
+    pub override_singers: bool,
+}
+
+impl Theme {
+    /// The colours the player always had
+    pub fn default_theme() -> Theme {
+        Theme {
+            name: "default".to_string(),
+            sung: lyrics::DEFAULT_SUNG_COLOR,
+            unsung: lyrics::DEFAULT_UNSUNG_COLOR,
+            active: None,
+            outline: None,
+            background_dim: 0,
+            osd: None,
+            override_singers: false,
+        }
+    }
+
+    /// Orange and white, told apart whatever the colour vision, over a
+    /// dimmed video
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "high-contrast".to_string(),
+            sung: (230, 159, 0),
+            unsung: (255, 255, 255),
+            active: Some((240, 228, 66)),
+            outline: Some((0, 0, 0)),
+            background_dim: 96,
+            osd: Some((255, 255, 255)),
+            override_singers: true,
+        }
+    }
+
+    /// Reads a theme from a JSON object, missing keys keep their value.
+    /// `active`, `outline` and `osd` are reset by `false`.
+    pub fn apply(&mut self, json: &Json) {
+        if let Some(sung) = find_color(json, "sung") {
+            self.sung = sung;
+        }
+        if let Some(unsung) = find_color(json, "unsung") {
+            self.unsung = unsung;
+        }
+        if let Some(active) = find_optional_color(json, "active") {
+            self.active = active;
+        }
+        if let Some(outline) = find_optional_color(json, "outline") {
+            self.outline = outline;
+        }
+        if let Some(dim) = json.find("background_dim").and_then(|j| j.as_u64()) {
+            self.background_dim = ::std::cmp::min(dim, 255) as u8;
+        }
+        if let Some(osd) = find_optional_color(json, "osd") {
+            self.osd = osd;
+        }
+        if let Some(override_singers) = json.find("override_singers")
+                                            .and_then(|j| j.as_boolean()) {
+            self.override_singers = override_singers;
+        }
+    }
+
+    /// Applies the `%color <key> <RRGGBB>` directives of a song
+    pub fn apply_song(&mut self, metadata: &Metadata) {
+        for &(ref key, color) in &metadata.colors {
+            match &**key {
+                "sung" => self.sung = color,
+                "unsung" => self.unsung = color,
+                "active" => self.active = Some(color),
+                "outline" => self.outline = Some(color),
+                "osd" => self.osd = Some(color),
+                _ => warn!("Unknown theme colour {}", key),
+            }
+        }
+    }
+
+    /// Sung and unsung colours of a syllable
+    pub fn syllable_colors(&self, song: &Song, syllable: &Syllable) -> (Rgb, Rgb) {
+        if self.override_singers || syllable.singers.is_empty() {
+            (self.sung, self.unsung)
+        } else {
+            song.syllable_colors(syllable)
+        }
+    }
+}
+
+fn find_color(json: &Json, key: &str) -> Option<Rgb> {
+    json.find(key).and_then(|j| j.as_string()).and_then(lyrics::parse_color)
+}
+
+/// Some(None) when the key is `false`
+fn find_optional_color(json: &Json, key: &str) -> Option<Option<Rgb>> {
+    match json.find(key) {
+        Some(&Json::Boolean(false)) => Some(None),
+        _ => find_color(json, key).map(Some),
+    }
+}
+
+/// Themes the player cycles through, the built-in ones first
+#[derive(Debug, Clone)]
+pub struct Themes {
+    themes: Vec<Theme>,
+    /// Theme chosen by the user
+    selected: usize,
+    /// Theme a song asked for with `%theme`, until the user picks another
+    song_theme: Option<usize>,
+}
+
+impl Default for Themes {
+    fn default() -> Themes {
+        Themes {
+            themes: vec![Theme::default_theme(), Theme::high_contrast()],
+            selected: 0,
+            song_theme: None,
+        }
+    }
+}
+
+impl Themes {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.themes.iter().position(|theme| theme.name == name)
+    }
+
+    /// Reads themes from a JSON object of themes by name. A theme starts as
+    /// a copy of the one named by its `base` key, the default one otherwise.
+    /// Built-in themes can be changed the same way.
+    pub fn apply(&mut self, json: &Json) {
+        let object = match json.as_object() {
+            Some(object) => object,
+            None => return,
+        };
+        for (name, theme_json) in object {
+            let base = theme_json.find("base")
+                                 .and_then(|j| j.as_string())
+                                 .and_then(|base| self.position(base));
+            let position = self.position(name);
+            let mut theme = match (position, base) {
+                (_, Some(base)) => self.themes[base].clone(),
+                (Some(position), None) => self.themes[position].clone(),
+                (None, None) => self.themes[0].clone(),
+            };
+            theme.name = name.clone();
+            theme.apply(theme_json);
+            match position {
+                Some(position) => self.themes[position] = theme,
+                None => self.themes.push(theme),
+            }
+        }
+    }
+
+    /// Selects the theme named `name`, returns false if there is none
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.position(name) {
+            Some(position) => {
+                self.selected = position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Uses the theme a song asks for, if it exists, until the next song
+    pub fn set_song_theme(&mut self, name: Option<&str>) {
+        let song_theme = name.and_then(|name| {
+            let position = self.position(name);
+            if position.is_none() {
+                warn!("Unknown theme {}", name);
+            }
+            position
+        });
+        self.song_theme = song_theme;
+    }
+
+    /// Selects the theme after the current one
+    pub fn cycle(&mut self) {
+        let current = self.song_theme.unwrap_or(self.selected);
+        self.selected = (current + 1) % self.themes.len();
+        self.song_theme = None;
+    }
+
+    /// The current theme, with the colours overridden by the song
+    pub fn current(&self, metadata: &Metadata) -> Theme {
+        let mut theme = self.themes[self.song_theme.unwrap_or(self.selected)].clone();
+        theme.apply_song(metadata);
+        theme
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_serialize::json::Json;
+
+    use lyrics::Metadata;
+    use super::Themes;
+
+    fn themes() -> Themes {
+        let mut themes = Themes::default();
+        themes.apply(&Json::from_str(r#"{"stage": {"base": "high-contrast", "sung": "ff0000"}}"#)
+                          .unwrap());
+        themes
+    }
+
+    #[test]
+    fn song_theme() {
+        let mut themes = themes();
+        assert!(themes.select("stage"));
+        themes.set_song_theme(Some("default"));
+        let mut metadata = Metadata::default();
+        assert_eq!(themes.current(&metadata).name, "default");
+        // %color directives override the song theme too
+        metadata.colors.push(("sung".to_string(), (1, 2, 3)));
+        assert_eq!(themes.current(&metadata).sung, (1, 2, 3));
+        // the next song goes back to the selected theme
+        themes.set_song_theme(None);
+        let theme = themes.current(&Metadata::default());
+        assert_eq!(theme.name, "stage");
+        assert_eq!(theme.sung, (255, 0, 0));
+        assert_eq!(theme.unsung, (255, 255, 255));
+    }
+
+    #[test]
+    fn unknown_theme() {
+        let mut themes = themes();
+        assert!(themes.select("high-contrast"));
+        assert!(!themes.select("missing"));
+        assert_eq!(themes.current(&Metadata::default()).name, "high-contrast");
+        themes.set_song_theme(Some("missing"));
+        assert_eq!(themes.current(&Metadata::default()).name, "high-contrast");
+    }
+
+    #[test]
+    fn cycle() {
+        let mut themes = themes();
+        let mut names = Vec::new();
+        for _ in 0..4 {
+            themes.cycle();
+            names.push(themes.current(&Metadata::default()).name);
+        }
+        assert_eq!(names, vec!["high-contrast", "stage", "default", "high-contrast"]);
+        // cycling starts from the song theme
+        themes.set_song_theme(Some("stage"));
+        themes.cycle();
+        assert_eq!(themes.current(&Metadata::default()).name, "default");
+    }
+}
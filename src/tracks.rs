@@ -0,0 +1,48 @@
+use mpv::Mpv;
+
+/// A track of the loaded file, as listed by mpv's `track-list` property
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub id: i64,
+    /// "video", "audio" or "sub"
+    pub kind: String,
+    pub codec: Option<String>,
+    /// Index of the stream in the file, as used by ffmpeg
+    pub ff_index: Option<i64>,
+    pub external: bool,
+    /// Video track holding the cover art of an audio file
+    pub albumart: bool,
+    pub title: Option<String>,
+}
+
+pub fn track_list(mpv: &Mpv) -> Vec<Track> {
+    let count = mpv.get_property::<i64>("track-list/count").unwrap_or(0);
+    (0..count)
+        .filter_map(|index| {
+            let property = |name: &str| format!("track-list/{}/{}", index, name);
+            let kind = match mpv.try_get_property_string(&property("type")) {
+                Some(kind) => kind,
+                None => return None,
+            };
+            Some(Track {
+                id: mpv.get_property::<i64>(&property("id")).unwrap_or(0),
+                kind: kind,
+                codec: mpv.try_get_property_string(&property("codec")),
+                ff_index: mpv.get_property::<i64>(&property("ff-index")).ok(),
+                external: mpv.try_get_property_string(&property("external"))
+                             .map(|external| external == "yes")
+                             .unwrap_or(false),
+                albumart: mpv.try_get_property_string(&property("albumart"))
+                             .map(|albumart| albumart == "yes")
+                             .unwrap_or(false),
+                title: mpv.try_get_property_string(&property("title")),
+            })
+        })
+        .collect()
+}
+
+/// Returns true if the file has no video track other than a cover art
+pub fn is_audio_only(tracks: &[Track]) -> bool {
+    tracks.iter().any(|track| track.kind == "audio") &&
+    !tracks.iter().any(|track| track.kind == "video" && !track.albumart)
+}
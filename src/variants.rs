@@ -0,0 +1,173 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use lyrics;
+use lyrics::{LyricsError, Song};
+
+/// Name of the variant read from `<video>.lyr`
+pub const ORIGINAL: &'static str = "original";
+
+/// A named version of the lyrics : original, romaji, a translation...
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    pub song: Song,
+}
+
+/// Every variant of the lyrics of a song. The primary variant is sung, the
+/// secondary one is displayed under it in a smaller font.
+#[derive(Debug, Clone)]
+pub struct Variants {
+    pub list: Vec<Variant>,
+    pub primary: usize,
+    pub secondary: Option<usize>,
+    /// Files the variants have been read from
+    pub files: Vec<PathBuf>,
+}
+
+struct VariantFiles {
+    name: String,
+    lyr: PathBuf,
+    frm: Option<PathBuf>,
+}
+
+impl Variants {
+    /// Builds variants holding a single song, for lyrics that don't come
+    /// from sidecar files
+    pub fn single(song: Song) -> Variants {
+        Variants {
+            list: vec![Variant {
+                           name: ORIGINAL.to_string(),
+                           song: song,
+                       }],
+            primary: 0,
+            secondary: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// Loads `<video>.lyr` and every `<video>.<variant>.lyr` next to it. A
+    /// variant is timed by its own `<video>.<variant>.frm` if there is one,
+    /// or by `<video>.frm` if it has the same syllable count. Translations
+    /// which don't match any frm file are loaded untimed, line by line.
+    pub fn load(video_path: &Path, fps: f64) -> Result<Variants, LyricsError> {
+        let mut variants = Variants {
+            list: Vec::new(),
+            primary: 0,
+            secondary: None,
+            files: Vec::new(),
+        };
+        let (_, original_frm) = lyrics::sidecar_paths(video_path);
+        for files in discover(video_path) {
+            let song = match files.frm {
+                Some(ref frm) => Song::load(&files.lyr, frm, fps),
+                None => Song::load(&files.lyr, &original_frm, fps),
+            };
+            let song = match song {
+                Ok(song) => song,
+                Err(LyricsError::SyllableCountMismatch { .. }) if files.frm.is_none() &&
+                                                                  files.name != ORIGINAL => {
+                    try!(Song::load_untimed(&files.lyr))
+                }
+                Err(e) => {
+                    if files.name == ORIGINAL {
+                        return Err(e);
+                    }
+                    warn!("Ignoring lyrics variant {} : {}", files.name, e);
+                    continue;
+                }
+            };
+            variants.files.push(files.lyr.clone());
+            variants.files.extend(files.frm);
+            variants.list.push(Variant {
+                name: files.name,
+                song: song,
+            });
+        }
+        if variants.list.is_empty() {
+            return Err(LyricsError::Io(io::Error::new(io::ErrorKind::NotFound,
+                                                      "no lyr file found")));
+        }
+        if !variants.files.contains(&original_frm) {
+            variants.files.push(original_frm);
+        }
+        Ok(variants)
+    }
+
+    pub fn primary(&self) -> &Variant {
+        &self.list[self.primary]
+    }
+
+    pub fn secondary(&self) -> Option<&Variant> {
+        self.secondary.map(|secondary| &self.list[secondary])
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.list.iter().position(|variant| variant.name == name)
+    }
+
+    pub fn cycle_primary(&mut self) {
+        self.primary = (self.primary + 1) % self.list.len();
+    }
+
+    /// Cycles the secondary variant through every variant, then none
+    pub fn cycle_secondary(&mut self) {
+        self.secondary = match self.secondary {
+            None => Some(0),
+            Some(secondary) if secondary + 1 < self.list.len() => Some(secondary + 1),
+            Some(_) => None,
+        };
+    }
+
+    /// Keeps the same primary and secondary variants as `previous`, after the
+    /// lyrics have been reloaded
+    pub fn keep_selection(&mut self, previous: &Variants) {
+        if let Some(primary) = self.find(&previous.primary().name) {
+            self.primary = primary;
+        }
+        self.secondary = previous.secondary().and_then(|secondary| self.find(&secondary.name));
+    }
+}
+
+/// Lists the lyr files of the video, the original one first
+fn discover(video_path: &Path) -> Vec<VariantFiles> {
+    let video_stem = match video_path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) => stem.to_string(),
+        None => return Vec::new(),
+    };
+    let directory = match video_path.parent() {
+        Some(directory) if directory != Path::new("") => directory,
+        _ => Path::new("."),
+    };
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut variants: Vec<VariantFiles> =
+        entries.filter_map(|entry| entry.ok())
+               .map(|entry| entry.path())
+               .filter(|path| path.extension().map(|e| e == "lyr").unwrap_or(false))
+               .filter_map(|lyr| {
+                   let name = match lyr.file_stem().and_then(|stem| stem.to_str()) {
+                       Some(stem) if stem == video_stem => ORIGINAL.to_string(),
+                       Some(stem) if stem.starts_with(&format!("{}.", video_stem)) => {
+                           stem[video_stem.len() + 1..].to_string()
+                       }
+                       _ => return None,
+                   };
+                   let frm = lyr.with_extension("frm");
+                   Some(VariantFiles {
+                       name: name,
+                       frm: if frm.exists() {
+                           Some(frm)
+                       } else {
+                           None
+                       },
+                       lyr: lyr,
+                   })
+               })
+               .collect();
+    variants.sort_by(|a, b| (a.name != ORIGINAL, &a.name).cmp(&(b.name != ORIGINAL, &b.name)));
+    variants
+}
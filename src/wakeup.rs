@@ -0,0 +1,33 @@
+use std::mem;
+use std::u32;
+use std::ptr;
+use std::os::raw as libc;
+
+use sdl2_sys::event::{SDL_Event, SDL_PushEvent, SDL_RegisterEvents, SDL_UserEvent};
+
+/// Registers the SDL user event pushed by `wake_up`, returns its type. It
+/// is passed as the data pointer of the mpv callbacks.
+pub fn register() -> Result<*mut libc::c_void, String> {
+    let event_type = unsafe { SDL_RegisterEvents(1) };
+    if event_type == u32::MAX {
+        return Err("no SDL user event left".to_string());
+    }
+    Ok(event_type as usize as *mut libc::c_void)
+}
+
+/// Wakeup and update callback of mpv : pushes an SDL user event of type
+/// `data`, which stops the main loop from waiting for events. mpv calls it
+/// from its own threads, where only SDL_PushEvent may be used.
+pub unsafe extern "C" fn wake_up(data: *mut libc::c_void) {
+    let user = SDL_UserEvent {
+        type_: data as usize as u32,
+        timestamp: 0,
+        windowID: 0,
+        code: 0,
+        data1: ptr::null_mut(),
+        data2: ptr::null_mut(),
+    };
+    let mut event: SDL_Event = mem::zeroed();
+    ptr::write(&mut event as *mut SDL_Event as *mut SDL_UserEvent, user);
+    SDL_PushEvent(&mut event);
+}
@@ -0,0 +1,161 @@
+use std::path::{Path, PathBuf};
+
+pub use self::imp::FileWatcher;
+
+/// On Linux the directories containing the files are watched with inotify :
+/// editors often save by writing a new file and renaming it over the old
+/// one, which a watch on the file itself would miss.
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::ffi::{CString, OsStr};
+    use std::mem;
+    use std::ptr;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    const IN_NONBLOCK: c_int = 0o4000;
+    const IN_CLOEXEC: c_int = 0o2000000;
+    const IN_MODIFY: u32 = 0x2;
+    const IN_CLOSE_WRITE: u32 = 0x8;
+    const IN_MOVED_TO: u32 = 0x80;
+    const IN_CREATE: u32 = 0x100;
+
+    #[repr(C)]
+    struct InotifyEvent {
+        wd: c_int,
+        mask: u32,
+        cookie: u32,
+        len: u32,
+    }
+
+    extern "C" {
+        fn inotify_init1(flags: c_int) -> c_int;
+        fn inotify_add_watch(fd: c_int, pathname: *const c_char, mask: u32) -> c_int;
+        fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize;
+        fn close(fd: c_int) -> c_int;
+    }
+
+    pub struct FileWatcher {
+        fd: c_int,
+        files: Vec<PathBuf>,
+    }
+
+    impl FileWatcher {
+        pub fn new(files: &[&Path]) -> Result<FileWatcher, ()> {
+            let fd = unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(());
+            }
+            let watcher = FileWatcher {
+                fd: fd,
+                files: files.iter().map(|file| file.to_path_buf()).collect(),
+            };
+            for file in files {
+                let directory = match file.parent() {
+                    Some(directory) if directory != Path::new("") => directory,
+                    _ => Path::new("."),
+                };
+                let directory = try!(CString::new(directory.as_os_str().as_bytes())
+                                         .map_err(|_| ()));
+                let mask = IN_MODIFY | IN_CLOSE_WRITE | IN_MOVED_TO | IN_CREATE;
+                if unsafe { inotify_add_watch(fd, directory.as_ptr(), mask) } < 0 {
+                    return Err(());
+                }
+            }
+            Ok(watcher)
+        }
+
+        /// Returns true if one of the watched files changed since the last call
+        pub fn changed(&mut self) -> bool {
+            let mut changed = false;
+            let mut buffer = [0u8; 4096];
+            loop {
+                let length = unsafe {
+                    read(self.fd, buffer.as_mut_ptr() as *mut c_void, buffer.len())
+                };
+                if length <= 0 {
+                    break;
+                }
+                let length = length as usize;
+                let mut offset = 0;
+                while offset + mem::size_of::<InotifyEvent>() <= length {
+                    let event: InotifyEvent = unsafe {
+                        ptr::read_unaligned(buffer.as_ptr().offset(offset as isize) as
+                                            *const InotifyEvent)
+                    };
+                    let name_start = offset + mem::size_of::<InotifyEvent>();
+                    let name = &buffer[name_start..name_start + event.len as usize];
+                    // the name is padded with null bytes
+                    let name = match name.iter().position(|&b| b == 0) {
+                        Some(end) => &name[..end],
+                        None => name,
+                    };
+                    let name = OsStr::from_bytes(name);
+                    if self.files.iter().any(|file| file.file_name() == Some(name)) {
+                        changed = true;
+                    }
+                    offset = name_start + event.len as usize;
+                }
+            }
+            changed
+        }
+    }
+
+    impl Drop for FileWatcher {
+        fn drop(&mut self) {
+            unsafe {
+                close(self.fd);
+            }
+        }
+    }
+}
+
+/// Elsewhere the modification dates of the files are compared
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::SystemTime;
+
+    pub struct FileWatcher {
+        files: Vec<(PathBuf, Option<SystemTime>)>,
+    }
+
+    fn modified(file: &Path) -> Option<SystemTime> {
+        fs::metadata(file).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    impl FileWatcher {
+        pub fn new(files: &[&Path]) -> Result<FileWatcher, ()> {
+            Ok(FileWatcher {
+                files: files.iter().map(|file| (file.to_path_buf(), modified(file))).collect(),
+            })
+        }
+
+        /// Returns true if one of the watched files changed since the last call
+        pub fn changed(&mut self) -> bool {
+            let mut changed = false;
+            for &mut (ref file, ref mut last_modified) in &mut self.files {
+                let modified = modified(file);
+                if modified != *last_modified {
+                    *last_modified = modified;
+                    changed = true;
+                }
+            }
+            changed
+        }
+    }
+}
+
+/// Watches the lyrics files of a video
+pub fn watch_lyrics(files: &[PathBuf]) -> Option<FileWatcher> {
+    let files: Vec<&Path> = files.iter().map(|file| file.as_path()).collect();
+    match FileWatcher::new(&files) {
+        Ok(watcher) => Some(watcher),
+        Err(_) => {
+            warn!("Failed to watch lyrics files, they won't be reloaded");
+            None
+        }
+    }
+}